@@ -2,6 +2,15 @@ use proc_macro2::{Group, Span, TokenStream, TokenTree};
 use quote::{quote, ToTokens};
 use syn::{spanned::Spanned, token::Mut, Data, DeriveInput, Fields, Ident, Item, Path};
 
+/// Whether the action name follows the spec (this has to be kept in sync with
+/// `neuro_sama::schema::is_valid_action_name`).
+fn is_valid_action_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
 fn derive_actions2(input: TokenStream) -> TokenStream {
     let data: DeriveInput = syn::parse2(input).unwrap();
     let name = data.ident;
@@ -12,6 +21,7 @@ fn derive_actions2(input: TokenStream) -> TokenStream {
     let mut ret1 = TokenStream::new();
     let mut meta = TokenStream::new();
     let mut names = TokenStream::new();
+    let mut literal_names = Vec::new();
     for variant in data.variants {
         let field = match variant.fields {
             Fields::Unit => None,
@@ -65,6 +75,23 @@ fn derive_actions2(input: TokenStream) -> TokenStream {
                     )
                 })
                 .unwrap();
+            if let syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(lit),
+                ..
+            }) = &name
+            {
+                let name = lit.value();
+                if !is_valid_action_name(&name) {
+                    panic!(
+                        "invalid action name `{}` for variant {}: action names should be lowercase, with words separated by underscores or dashes",
+                        name, ident
+                    );
+                }
+                if literal_names.contains(&name) {
+                    panic!("duplicate action name `{}` for variant {}", name, ident);
+                }
+                literal_names.push(name);
+            }
             ret.extend(quote! {
                 impl neuro_sama::game::Action for #ty {
                     fn name() -> &'static str {
@@ -76,13 +103,24 @@ fn derive_actions2(input: TokenStream) -> TokenStream {
                 }
             });
             ret1.extend(quote! {
-                #name => <#ty as neuro_sama::serde::Deserialize<'_>>::deserialize(de).map(Self::#ident),
+                #name => {
+                    let value = <#ty as neuro_sama::serde::Deserialize<'_>>::deserialize(de)?;
+                    neuro_sama::game::constraints::ConstraintsOf::<#ty>(::core::marker::PhantomData)
+                        .check(&value)
+                        .map_err(D::Error::custom)?;
+                    Ok(Self::#ident(value))
+                }
             });
             meta.extend(quote! {
                 neuro_sama::schema::Action {
                     name: #name.into(),
                     description: #desc.trim().into(),
-                    schema: neuro_sama::schemars::schema_for!(#ty),
+                    schema: {
+                        let mut schema = settings.clone().into_generator().into_root_schema_for::<#ty>();
+                        neuro_sama::game::constraints::ConstraintsOf::<#ty>(::core::marker::PhantomData)
+                            .constrain_schema(&mut schema);
+                        schema
+                    },
                 },
             });
             names.extend(quote! { #name.into(), });
@@ -94,6 +132,9 @@ fn derive_actions2(input: TokenStream) -> TokenStream {
         impl<'de> neuro_sama::game::Actions<'de> for #name where Self: 'de  {
             fn deserialize<D: neuro_sama::serde::Deserializer<'de>>(discriminant: &str, de: D) -> Result<Self, D::Error> {
                 use neuro_sama::serde::de::Error as _;
+                // the fallback for action types without constraints
+                #[allow(unused_imports)]
+                use neuro_sama::game::constraints::NoConstraints as _;
                 match discriminant {
                     #ret1
                     _ => Err(D::Error::custom(format!("unexpected action: `{discriminant}`"))),
@@ -102,6 +143,11 @@ fn derive_actions2(input: TokenStream) -> TokenStream {
         }
         impl neuro_sama::game::ActionMetadata for #name {
             fn actions() -> Vec<neuro_sama::schema::Action> {
+                Self::actions_with(&Default::default())
+            }
+            fn actions_with(settings: &neuro_sama::schemars::gen::SchemaSettings) -> Vec<neuro_sama::schema::Action> {
+                #[allow(unused_imports)]
+                use neuro_sama::game::constraints::NoConstraints as _;
                 vec![#meta]
             }
             fn names() -> Vec<std::borrow::Cow<'static, str>> {
@@ -112,13 +158,324 @@ fn derive_actions2(input: TokenStream) -> TokenStream {
     ret
 }
 
-fn generic_mutability2(attr: TokenStream, input: TokenStream) -> TokenStream {
+/// Apply a serde `rename_all` rule to a field name.
+fn rename_field(name: &str, rule: &str) -> String {
+    let pascal = || {
+        name.split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|c| c.to_ascii_uppercase().to_string() + chars.as_str())
+                    .unwrap_or_default()
+            })
+            .collect::<String>()
+    };
+    match rule {
+        "lowercase" | "snake_case" => name.to_owned(),
+        "UPPERCASE" | "SCREAMING_SNAKE_CASE" => name.to_ascii_uppercase(),
+        "PascalCase" => pascal(),
+        "camelCase" => {
+            let pascal = pascal();
+            let mut chars = pascal.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_lowercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        }
+        "kebab-case" => name.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => name.to_ascii_uppercase().replace('_', "-"),
+        _ => panic!("unknown rename rule `{}`", rule),
+    }
+}
+
+/// Skip the value of a nested meta item that isn't of interest.
+fn skip_meta(meta: &syn::meta::ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|meta| skip_meta(&meta))?;
+    }
+    Ok(())
+}
+
+/// Get the serde `rename`/`rename_all` value for deserialization from a `#[serde(...)]`
+/// attribute.
+fn serde_rename(attrs: &[syn::Attribute], key: &str) -> Option<String> {
+    let mut ret = None;
+    for attr in attrs.iter().filter(|x| x.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if !meta.path.is_ident(key) {
+                return skip_meta(&meta);
+            }
+            if meta.input.peek(syn::Token![=]) {
+                ret = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+                return Ok(());
+            }
+            meta.parse_nested_meta(|meta| {
+                if meta.path.is_ident("deserialize") {
+                    ret = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+                    Ok(())
+                } else {
+                    skip_meta(&meta)
+                }
+            })
+        })
+        .unwrap();
+    }
+    ret
+}
+
+/// The bounds of a range expression, as `Option<f64>` (for the schema) and `Option<&dyn Display>`
+/// (for the error message) expressions, along with the comparisons that check `value`.
+fn range_bounds(range: &syn::ExprRange, cast: TokenStream) -> (TokenStream, TokenStream) {
+    let inclusive = matches!(range.limits, syn::RangeLimits::Closed(_));
+    let (start, end) = (range.start.as_deref(), range.end.as_deref());
+    let bound = |x: Option<&syn::Expr>| match x {
+        Some(x) => quote! { Some((#x) as #cast) },
+        None => quote! { None },
+    };
+    let (min, max) = (bound(start), bound(end));
+    let display = |x: Option<&syn::Expr>| match x {
+        Some(x) => quote! { Some(&(#x) as &dyn ::core::fmt::Display) },
+        None => quote! { None },
+    };
+    let (min_display, max_display) = (display(start), display(end));
+    let mut cond = quote! { true };
+    if let Some(start) = start {
+        cond.extend(quote! { && value >= #start });
+    }
+    match (end, inclusive) {
+        (Some(end), true) => cond.extend(quote! { && value <= #end }),
+        (Some(end), false) => cond.extend(quote! { && value < #end }),
+        (None, _) => {}
+    }
+    (
+        quote! { #min, #max, #inclusive },
+        quote! {
+            (#cond, neuro_sama::game::constraints::describe_range(#min_display, #max_display, #inclusive))
+        },
+    )
+}
+
+/// Whether a range with literal integer bounds is empty (a missing start counts as zero).
+fn is_empty_range(range: &syn::ExprRange) -> bool {
+    let literal = |x: Option<&syn::Expr>| match x {
+        None => Some(0),
+        Some(syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(x),
+            ..
+        })) => x.base10_parse::<u128>().ok(),
+        Some(_) => None,
+    };
+    let Some(end) = range.end.as_deref() else {
+        return false;
+    };
+    match (literal(range.start.as_deref()), literal(Some(end))) {
+        (Some(start), Some(end)) => match range.limits {
+            syn::RangeLimits::HalfOpen(_) => start >= end,
+            syn::RangeLimits::Closed(_) => start > end,
+        },
+        _ => false,
+    }
+}
+
+fn derive_constraints2(input: TokenStream) -> TokenStream {
+    let data: DeriveInput = syn::parse2(input).unwrap();
+    let name = data.ident;
+    let (impl_generics, ty_generics, where_clause) = data.generics.split_for_impl();
+    let rename_all = serde_rename(&data.attrs, "rename_all");
+    let Data::Struct(data) = data.data else {
+        panic!("#[derive(Constraints)] is only supported on structs")
+    };
+    let Fields::Named(fields) = data.fields else {
+        panic!("#[derive(Constraints)] is only supported on structs with named fields")
+    };
+    let mut schema = TokenStream::new();
+    let mut check = TokenStream::new();
+    for field in fields.named {
+        let ident = field.ident.unwrap();
+        let field_name = ident.to_string();
+        let field_name = field_name.trim_start_matches("r#");
+        let field_name =
+            serde_rename(&field.attrs, "rename").unwrap_or_else(|| match &rename_all {
+                Some(rule) => rename_field(field_name, rule),
+                None => field_name.to_owned(),
+            });
+        let mut field_schema = TokenStream::new();
+        // each constraint gets its own block, since they shadow `value`
+        let mut field_check = TokenStream::new();
+        for attr in field.attrs.iter().filter(|x| x.path().is_ident("neuro")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("range") {
+                    let range = meta.value()?.parse::<syn::ExprRange>()?;
+                    let (bounds, cond) = range_bounds(&range, quote! { f64 });
+                    field_schema.extend(quote! {
+                        neuro_sama::game::constraints::range(schema, #bounds);
+                    });
+                    field_check.extend(quote! {{
+                        let value = *value;
+                        let (ok, expected) = #cond;
+                        if !ok {
+                            return Err(format!("`{}` must be {}", #field_name, expected));
+                        }
+                    }});
+                } else if meta.path.is_ident("len") {
+                    let range = meta.value()?.parse::<syn::ExprRange>()?;
+                    if is_empty_range(&range) {
+                        return Err(meta.error("empty `len` range, no length would be allowed"));
+                    }
+                    let (bounds, cond) = range_bounds(&range, quote! { usize });
+                    field_schema.extend(quote! {
+                        neuro_sama::game::constraints::len(schema, #bounds);
+                    });
+                    field_check.extend(quote! {{
+                        let unit = neuro_sama::game::constraints::length_unit(value);
+                        let value = neuro_sama::game::constraints::Length::length(value);
+                        let (ok, expected) = #cond;
+                        if !ok {
+                            return Err(format!("`{}` must have {} {}", #field_name, expected, unit));
+                        }
+                    }});
+                } else if meta.path.is_ident("one_of") {
+                    let values = meta.value()?.parse::<syn::ExprArray>()?.elems;
+                    let values = values.iter().collect::<Vec<_>>();
+                    field_schema.extend(quote! {
+                        neuro_sama::game::constraints::one_of(
+                            schema,
+                            vec![#(neuro_sama::game::constraints::to_value(&#values)),*],
+                        );
+                    });
+                    field_check.extend(quote! {{
+                        if !(false #(|| *value == #values)*) {
+                            let values = [#(neuro_sama::game::constraints::to_value(&#values)),*];
+                            return Err(format!(
+                                "`{}` must be {}",
+                                #field_name,
+                                neuro_sama::game::constraints::describe_values(&values),
+                            ));
+                        }
+                    }});
+                } else {
+                    return Err(meta.error("unknown constraint, expected `range`, `len` or `one_of`"));
+                }
+                Ok(())
+            })
+            .unwrap();
+        }
+        if field_schema.is_empty() {
+            continue;
+        }
+        schema.extend(quote! {{
+            let schema = neuro_sama::game::constraints::property(schema, #field_name);
+            #field_schema
+        }});
+        let is_option = matches!(
+            &field.ty,
+            syn::Type::Path(x) if x.qself.is_none()
+                && x.path.segments.last().is_some_and(|x| x.ident == "Option")
+        );
+        if is_option {
+            check.extend(quote! {
+                if let Some(value) = &self.#ident {
+                    #field_check
+                }
+            });
+        } else {
+            check.extend(quote! {
+                {
+                    let value = &self.#ident;
+                    #field_check
+                }
+            });
+        }
+    }
+    quote! {
+        impl #impl_generics neuro_sama::game::Constraints for #name #ty_generics #where_clause {
+            fn constrain_schema(schema: &mut neuro_sama::schemars::schema::RootSchema) {
+                #schema
+            }
+            #[allow(
+                unused_comparisons,
+                clippy::absurd_extreme_comparisons,
+                clippy::manual_range_contains,
+                clippy::nonminimal_bool
+            )]
+            fn check(&self) -> Result<(), String> {
+                #check
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Which variant of a trait/struct/impl to generate.
+#[derive(Clone, Copy)]
+enum Variant {
+    /// Take `&mut self` instead of `&self`.
+    Mut,
+    /// Make `handle_action` and `handle_message` return futures.
+    Async,
+}
+
+impl Variant {
+    fn rename(self, ident: &str) -> Option<&'static str> {
+        match (self, ident) {
+            (Self::Mut, "ForceActionsBuilder") => Some("ForceActionsBuilderMut"),
+            (Self::Mut, "send_ws_command") => Some("send_ws_command_mut"),
+            (Self::Async, "ForceActionsBuilder") => Some("ForceActionsBuilderAsync"),
+            (Self::Async, "send_ws_command") => Some("send_ws_command_async"),
+            _ => None,
+        }
+    }
+
+    fn doc(self, ident: &Ident) -> String {
+        match self {
+            Self::Mut => format!(
+                "A mutable version of [`{}`]. See [`{}`] docs for examples.",
+                ident, ident
+            ),
+            Self::Async => format!(
+                "An async version of [`{}`]. See [`{}`] docs for examples.",
+                ident, ident
+            ),
+        }
+    }
+}
+
+/// Turn the return type `T` of a trait method into `impl Future<Output = T>`, optionally wrapping
+/// the default body into an async block.
+fn make_async(x: &mut syn::TraitItemFn) {
+    let output = match &x.sig.output {
+        syn::ReturnType::Default => quote! { () },
+        syn::ReturnType::Type(_, ty) => ty.to_token_stream(),
+    };
+    x.sig.output = syn::parse_quote! {
+        -> impl ::core::future::Future<Output = #output>
+    };
+    if let Some(block) = &mut x.default {
+        let stmts = &block.stmts;
+        *block = syn::parse_quote! {{
+            async move { #(#stmts)* }
+        }};
+    }
+}
+
+fn generic_variant(attr: TokenStream, input: TokenStream, variant: Variant) -> TokenStream {
     let inp: Item = syn::parse2(input).unwrap();
     let mut attr = attr.into_iter();
     let ident = Ident::new(&attr.next().unwrap().to_string(), Span::call_site());
+    // the other variant's attribute shouldn't be copied over
+    let is_variant_attr = |x: &syn::Attribute| {
+        x.path()
+            .segments
+            .last()
+            .is_some_and(|x| x.ident == "generic_mutability" || x.ident == "generic_asyncness")
+    };
     let (ident, out) = match &inp {
         Item::Struct(inp) => {
             let mut out = inp.clone();
+            out.attrs.retain(|x| !is_variant_attr(x));
             let ident2 = Ident::new(&attr.nth(1).unwrap().to_string(), Span::call_site());
             match out
                 .generics
@@ -139,6 +496,7 @@ fn generic_mutability2(attr: TokenStream, input: TokenStream) -> TokenStream {
         }
         Item::Impl(inp) => {
             let mut out = inp.clone();
+            out.attrs.retain(|x| !is_variant_attr(x));
             let ident2 = Ident::new(&attr.nth(1).unwrap().to_string(), Span::call_site());
             match out
                 .generics
@@ -167,10 +525,8 @@ fn generic_mutability2(attr: TokenStream, input: TokenStream) -> TokenStream {
             let mut out = inp.clone();
             out.ident = ident;
             out.attrs.retain(|x| {
-                !matches!(
-                    x.path().to_token_stream().to_string().as_str(),
-                    "generic_mutability" | "doc",
-                )
+                !is_variant_attr(x)
+                    && !matches!(x.path().to_token_stream().to_string().as_str(), "doc")
             });
             if attr.next().is_some() {
                 if let syn::TypeParamBound::Trait(t) = out.supertraits.first_mut().unwrap() {
@@ -180,21 +536,30 @@ fn generic_mutability2(attr: TokenStream, input: TokenStream) -> TokenStream {
             }
             for item in &mut out.items {
                 if let syn::TraitItem::Fn(x) = item {
-                    if let Some(arg) = x.sig.inputs.first_mut() {
-                        match arg {
-                            syn::FnArg::Receiver(x)
-                                if x.mutability.is_none() && x.reference.is_some() =>
-                            {
-                                x.mutability = Some(Mut {
-                                    span: x.reference.as_ref().unwrap().0.span,
-                                });
-                                if let syn::Type::Reference(x) = &mut *x.ty {
-                                    x.mutability = Some(Mut {
-                                        span: x.elem.span(),
-                                    })
+                    match variant {
+                        Variant::Mut => {
+                            if let Some(arg) = x.sig.inputs.first_mut() {
+                                match arg {
+                                    syn::FnArg::Receiver(x)
+                                        if x.mutability.is_none() && x.reference.is_some() =>
+                                    {
+                                        x.mutability = Some(Mut {
+                                            span: x.reference.as_ref().unwrap().0.span,
+                                        });
+                                        if let syn::Type::Reference(x) = &mut *x.ty {
+                                            x.mutability = Some(Mut {
+                                                span: x.elem.span(),
+                                            })
+                                        }
+                                    }
+                                    _ => {}
                                 }
                             }
-                            _ => {}
+                        }
+                        Variant::Async => {
+                            if x.sig.ident == "handle_action" || x.sig.ident == "handle_message" {
+                                make_async(x);
+                            }
                         }
                     }
                     // panic!("{}", x.to_token_stream().to_string());
@@ -204,37 +569,47 @@ fn generic_mutability2(attr: TokenStream, input: TokenStream) -> TokenStream {
         }
         _ => panic!(),
     };
-    fn hack_stream(x: TokenStream) -> TokenStream {
-        x.into_iter().map(hack_tree).collect()
+    fn hack_stream(x: TokenStream, variant: Variant) -> TokenStream {
+        let mut ret = TokenStream::new();
+        let mut x = x.into_iter().peekable();
+        let mut after_fn = false;
+        while let Some(tree) = x.next() {
+            let is_async_call = !after_fn
+                && matches!(&tree, TokenTree::Ident(ident) if ident == "handle_action" || ident == "handle_message");
+            after_fn = matches!(&tree, TokenTree::Ident(ident) if ident == "fn");
+            ret.extend([hack_tree(tree, variant)]);
+            // the async versions of `handle_action` and `handle_message` have to be awaited
+            if let (Variant::Async, true, Some(TokenTree::Group(group))) =
+                (variant, is_async_call, x.peek())
+            {
+                if group.delimiter() == proc_macro2::Delimiter::Parenthesis {
+                    ret.extend([hack_tree(x.next().unwrap(), variant)]);
+                    ret.extend(quote! { .await });
+                }
+            }
+        }
+        ret
     }
-    fn hack_tree(x: TokenTree) -> TokenTree {
+    fn hack_tree(x: TokenTree, variant: Variant) -> TokenTree {
         match x {
             TokenTree::Group(x) => {
                 let del = x.delimiter();
                 let st = x.stream();
-                let st = hack_stream(st);
+                let st = hack_stream(st, variant);
                 TokenTree::Group(Group::new(del, st))
             }
-            TokenTree::Ident(ref ident) => match ident.to_string().as_str() {
-                "ForceActionsBuilder" => {
-                    TokenTree::Ident(Ident::new("ForceActionsBuilderMut", Span::call_site()))
-                }
-                "send_ws_command" => {
-                    TokenTree::Ident(Ident::new("send_ws_command_mut", Span::call_site()))
-                }
-                _ => x,
+            TokenTree::Ident(ref ident) => match variant.rename(&ident.to_string()) {
+                Some(renamed) => TokenTree::Ident(Ident::new(renamed, Span::call_site())),
+                None => x,
             },
             TokenTree::Punct(_) => x,
             TokenTree::Literal(_) => x,
         }
     }
-    let out = hack_stream(out);
+    let out = hack_stream(out, variant);
 
     if let Some(ident) = ident {
-        let doc = format!(
-            "A mutable version of [`{}`]. See [`{}`] docs for examples.",
-            ident, ident
-        );
+        let doc = variant.doc(&ident);
         quote! {
             #[doc = #doc]
             #out
@@ -248,17 +623,86 @@ fn generic_mutability2(attr: TokenStream, input: TokenStream) -> TokenStream {
     }
 }
 
+fn two_phase2(input: TokenStream) -> TokenStream {
+    let mut inp: syn::ItemImpl = syn::parse2(input).unwrap();
+    let tr = inp
+        .trait_
+        .as_ref()
+        .and_then(|(_, path, _)| path.segments.last())
+        .map(|x| x.ident.to_string());
+    let ret = quote! {
+        ::core::result::Result<
+            ::core::option::Option<impl 'static + ::core::convert::Into<::std::borrow::Cow<'static, str>>>,
+            ::core::option::Option<impl 'static + ::core::convert::Into<::std::borrow::Cow<'static, str>>>,
+        >
+    };
+    let item: syn::ImplItem = match tr.as_deref() {
+        Some("Game") => syn::parse_quote! {
+            fn handle_action<'a>(&self, action: Self::Actions<'a>) -> #ret {
+                neuro_sama::game::TwoPhaseGame::validate_action(self, action)
+            }
+        },
+        Some("GameMut") => syn::parse_quote! {
+            fn handle_action<'a>(&mut self, action: Self::Actions<'a>) -> #ret {
+                neuro_sama::game::TwoPhaseGameMut::validate_action(self, action)
+            }
+        },
+        Some("AsyncGame") => syn::parse_quote! {
+            async fn handle_action<'a>(&self, action: Self::Actions<'a>) -> #ret {
+                neuro_sama::game::AsyncTwoPhaseGame::validate_action(self, action)
+            }
+        },
+        _ => panic!("#[two_phase] is only supported on impls of Game, GameMut or AsyncGame"),
+    };
+    if inp
+        .items
+        .iter()
+        .any(|x| matches!(x, syn::ImplItem::Fn(x) if x.sig.ident == "handle_action"))
+    {
+        panic!("#[two_phase] implements handle_action, so it must not be implemented manually");
+    }
+    inp.items.insert(0, item);
+    inp.into_token_stream()
+}
+
 /// See the `neuro_sama` crate for more info.
 #[proc_macro_derive(Actions, attributes(name))]
 pub fn derive_actions(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     derive_actions2(input.into()).into()
 }
 
+/// See the `neuro_sama::game::Constraints` trait for more info.
+#[proc_macro_derive(Constraints, attributes(neuro))]
+pub fn derive_constraints(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    derive_constraints2(input.into()).into()
+}
+
 #[proc_macro_attribute]
 #[doc(hidden)]
 pub fn generic_mutability(
     attr: proc_macro::TokenStream,
     input: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    generic_mutability2(attr.into(), input.into()).into()
+    generic_variant(attr.into(), input.into(), Variant::Mut).into()
+}
+
+#[proc_macro_attribute]
+#[doc(hidden)]
+pub fn generic_asyncness(
+    attr: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    generic_variant(attr.into(), input.into(), Variant::Async).into()
+}
+
+/// See the `neuro_sama::game::TwoPhaseGame` trait for more info.
+#[proc_macro_attribute]
+pub fn two_phase(
+    attr: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    if !attr.is_empty() {
+        panic!("#[two_phase] doesn't take any arguments");
+    }
+    two_phase2(input.into()).into()
 }
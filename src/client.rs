@@ -0,0 +1,155 @@
+//! Ready-made WebSocket clients, for when you don't want to bring your own IO.
+//!
+//! Each client owns the WebSocket connection, forwards the messages your [`Game`] sends via
+//! [`Game::send_command`] to the server, calls [`Api::initialize`] once the connection is
+//! established and passes incoming messages to [`Api::handle_message`].
+//!
+//! Available clients:
+//!
+//! - `client::tokio` (requires the `"tokio"` feature) - an async client based on
+//!   `tokio-tungstenite`.
+//! - `client::blocking` (requires the `"blocking"` feature) - a blocking client based on plain
+//!   `tungstenite` that runs on its own thread.
+//!
+//! Both clients can optionally reconnect with exponential backoff (see [`Backoff`]). After a
+//! reconnect, [`Api::resume`] is called, which registers the actions that were registered in the
+//! [`Session`] before the connection was lost again (or calls [`Game::reregister_actions`] if
+//! there is no session).
+//!
+//! [`Game`]: crate::game::Game
+//! [`Game::send_command`]: crate::game::Game::send_command
+//! [`Game::reregister_actions`]: crate::game::Game::reregister_actions
+//! [`Api::initialize`]: crate::game::Api::initialize
+//! [`Api::resume`]: crate::game::Api::resume
+//! [`Session`]: crate::game::Session
+//! [`Api::handle_message`]: crate::game::Api::handle_message
+use std::time::Duration;
+
+use thiserror::Error;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+/// An error that occured while running a client.
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum Error {
+    /// A WebSocket error
+    #[error("websocket error: {0}")]
+    WebSocket(#[source] Box<tungstenite::Error>),
+    /// An error returned by one of the [`Api`](crate::game::Api) methods
+    #[error("api error: {0}")]
+    Api(
+        #[from]
+        #[source]
+        crate::game::Error,
+    ),
+}
+
+impl From<tungstenite::Error> for Error {
+    fn from(err: tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(err))
+    }
+}
+
+/// An event emitted by a running client.
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum Event {
+    /// The connection has been established and [`Api::initialize`](crate::game::Api::initialize)
+    /// has been called (or, after a reconnect, [`Api::resume`](crate::game::Api::resume)).
+    Connected,
+    /// The connection has been closed.
+    Disconnected,
+    /// The client is going to try to reconnect after waiting for `delay`.
+    Reconnecting {
+        /// The number of the reconnection attempt, starting at 1.
+        attempt: u32,
+        /// How long the client is going to wait before reconnecting.
+        delay: Duration,
+    },
+    /// A non-fatal error. If the error is fatal, it will be followed by [`Event::Disconnected`].
+    Error(Error),
+}
+
+/// An error returned when trying to send a message to a client that isn't running anymore. The
+/// message that couldn't be sent is returned back.
+#[derive(Debug, Error)]
+#[error("the client is not running")]
+pub struct SendError(pub tungstenite::Message);
+
+/// Exponential backoff settings for reconnecting clients.
+///
+/// The delay before the `n`th attempt (starting at zero) is `initial_delay * multiplier^n`,
+/// capped at `max_delay`. By default, the delay starts at half a second, doubles after every
+/// failed attempt, never exceeds 30 seconds, and the client keeps trying forever.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backoff {
+    initial_delay: Duration,
+    max_delay: Duration,
+    multiplier: f64,
+    max_attempts: Option<u32>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            max_attempts: None,
+        }
+    }
+}
+
+impl Backoff {
+    /// Create the default backoff settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The delay before the first reconnection attempt.
+    #[must_use]
+    pub fn with_initial_delay(mut self, initial_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self
+    }
+
+    /// The maximum delay between reconnection attempts.
+    #[must_use]
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// How much the delay is multiplied by after each failed attempt.
+    #[must_use]
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Give up after this many consecutive failed attempts.
+    #[must_use]
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// The delay before the reconnection attempt number `attempt` (starting at zero), or `None`
+    /// if the client should give up.
+    pub fn delay(&self, attempt: u32) -> Option<Duration> {
+        if self.max_attempts.is_some_and(|max| attempt >= max) {
+            return None;
+        }
+        let secs = self.initial_delay.as_secs_f64()
+            * self.multiplier.powi(attempt.try_into().unwrap_or(i32::MAX));
+        Some(
+            Duration::try_from_secs_f64(secs)
+                .unwrap_or(self.max_delay)
+                .min(self.max_delay),
+        )
+    }
+}
@@ -0,0 +1,462 @@
+//! A blocking client based on plain `tungstenite`, for games that don't use an async runtime.
+//!
+//! The client spawns an IO thread that owns the WebSocket, forwards the queued outgoing messages
+//! and calls [`Api::handle_message`] for the incoming ones. Since [`Api::handle_message`] is called
+//! from that thread, your game object will usually be an `Arc` of something thread-safe.
+//!
+//! Only plain `ws://` URLs are supported.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use std::sync::Arc;
+//! use neuro_sama::client::{self, blocking::Sender};
+//!
+//! struct MyGame(Sender);
+//!
+//! impl neuro_sama::game::Game for MyGame {
+//!     // ...
+//!     fn send_command(&self, message: tungstenite::Message) {
+//!         let _ = self.0.send(message);
+//!     }
+//! }
+//!
+//! let (tx, rx) = client::blocking::channel();
+//! let game = Arc::new(MyGame(tx));
+//! let client = client::blocking::connect(game.clone(), rx, "ws://127.0.0.1:8000")?;
+//! loop {
+//!     while let Some(event) = client.try_next_event() {
+//!         println!("{event:?}");
+//!     }
+//!     // run a game frame
+//! }
+//! client.shutdown();
+//! ```
+use std::{
+    io::ErrorKind,
+    net::TcpStream,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use tungstenite::{stream::MaybeTlsStream, WebSocket};
+
+use super::{Backoff, Error, Event, SendError};
+use crate::game::Api;
+
+/// How long the IO thread blocks on reading before checking for outgoing messages.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Create a new channel for sending messages to the client. The [`Sender`] should be used in your
+/// [`Game::send_command`](crate::game::Game::send_command) implementation, and the [`Receiver`]
+/// should be passed to [`connect`].
+pub fn channel() -> (Sender, Receiver) {
+    let (tx, rx) = mpsc::channel();
+    (Sender(tx), Receiver(rx))
+}
+
+/// The sending half of the client's message queue. See [`channel`].
+#[derive(Clone, Debug)]
+pub struct Sender(mpsc::Sender<tungstenite::Message>);
+
+impl Sender {
+    /// Queue a message for sending. This fails if the client has been shut down.
+    pub fn send(&self, message: tungstenite::Message) -> Result<(), SendError> {
+        self.0.send(message).map_err(|err| SendError(err.0))
+    }
+}
+
+/// The receiving half of the client's message queue. See [`channel`].
+#[derive(Debug)]
+pub struct Receiver(mpsc::Receiver<tungstenite::Message>);
+
+/// Connect to `url`, call [`Api::initialize`] and spawn the IO thread.
+///
+/// Messages queued before the connection is established are discarded, since the `startup`
+/// command sent by [`Api::initialize`] would clear them anyway.
+pub fn connect<G: Api + Send + 'static>(
+    game: G,
+    outgoing: Receiver,
+    url: &str,
+) -> Result<Client, Error> {
+    connect_inner(game, outgoing, url, None)
+}
+
+/// Same as [`connect`], but reconnect with the specified backoff settings when the connection is
+/// lost.
+///
+/// After a reconnect, [`Api::resume`] is called. Messages sent while the client was disconnected
+/// are discarded, unless they were queued by an [`Outbox`](crate::game::Outbox).
+pub fn connect_with_reconnect<G: Api + Send + 'static>(
+    game: G,
+    outgoing: Receiver,
+    url: &str,
+    backoff: Backoff,
+) -> Result<Client, Error> {
+    connect_inner(game, outgoing, url, Some(backoff))
+}
+
+fn connect_inner<G: Api + Send + 'static>(
+    game: G,
+    outgoing: Receiver,
+    url: &str,
+    reconnect: Option<Backoff>,
+) -> Result<Client, Error> {
+    let ws = open(url)?;
+    let rx = outgoing.0;
+    while rx.try_recv().is_ok() {}
+    game.initialize()?;
+    let (events_tx, events) = mpsc::channel();
+    let _ = events_tx.send(Event::Connected);
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = std::thread::spawn({
+        let url = url.to_owned();
+        let io = Io {
+            rx,
+            events: events_tx,
+            stop: stop.clone(),
+        };
+        move || io.run(game, ws, &url, reconnect)
+    });
+    Ok(Client {
+        events,
+        stop,
+        thread: Some(thread),
+    })
+}
+
+fn open(url: &str) -> Result<WebSocket<MaybeTlsStream<TcpStream>>, Error> {
+    let (ws, _) = tungstenite::connect(url)?;
+    if let MaybeTlsStream::Plain(stream) = ws.get_ref() {
+        stream
+            .set_read_timeout(Some(POLL_INTERVAL))
+            .map_err(tungstenite::Error::Io)?;
+    }
+    Ok(ws)
+}
+
+/// The reason [`Io::serve`] returned.
+enum Exit {
+    /// The client has been shut down.
+    Shutdown,
+    /// The server closed the connection.
+    Closed,
+    /// The connection was lost due to an error.
+    Error(tungstenite::Error),
+}
+
+/// The state of the IO thread.
+struct Io {
+    rx: mpsc::Receiver<tungstenite::Message>,
+    events: mpsc::Sender<Event>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Io {
+    fn run<G: Api>(
+        mut self,
+        game: G,
+        mut ws: WebSocket<MaybeTlsStream<TcpStream>>,
+        url: &str,
+        reconnect: Option<Backoff>,
+    ) {
+        loop {
+            match self.serve(&game, &mut ws) {
+                Exit::Shutdown => {
+                    let _ = self.events.send(Event::Disconnected);
+                    return;
+                }
+                Exit::Closed => {}
+                Exit::Error(err) => {
+                    let _ = self.events.send(Event::Error(err.into()));
+                }
+            }
+            if let Some(session) = game.session() {
+                session.set_connected(false);
+            }
+            let _ = self.events.send(Event::Disconnected);
+            let Some(backoff) = &reconnect else {
+                return;
+            };
+            match self.reconnect(&game, backoff, url) {
+                Some(new_ws) => ws = new_ws,
+                None => return,
+            }
+            let _ = self.events.send(Event::Connected);
+        }
+    }
+
+    /// Serve the connection until it's closed.
+    fn serve<G: Api>(&mut self, game: &G, ws: &mut WebSocket<MaybeTlsStream<TcpStream>>) -> Exit {
+        loop {
+            let mut closing = self.stop.load(Ordering::Acquire);
+            loop {
+                match self.rx.try_recv() {
+                    Ok(msg) => {
+                        if let Err(err) = ws.write(msg) {
+                            return Exit::Error(err);
+                        }
+                    }
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    // all senders have been dropped, nothing else will be sent
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        closing = true;
+                        break;
+                    }
+                }
+            }
+            if closing {
+                let _ = ws.close(None);
+                let _ = ws.flush();
+                return Exit::Shutdown;
+            }
+            let res = match ws.flush() {
+                Ok(()) => ws.read(),
+                Err(err) => Err(err),
+            };
+            match res {
+                Ok(msg) => {
+                    if let Err(err) = game.handle_message(msg) {
+                        // this could happen if the message is malformed, unknown commands are passed
+                        // to `Game::unknown_command` instead
+                        let _ = self.events.send(Event::Error(err.into()));
+                    }
+                }
+                Err(tungstenite::Error::Io(err))
+                    if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                    return Exit::Closed
+                }
+                Err(err) => return Exit::Error(err),
+            }
+        }
+    }
+
+    /// Try to reconnect until it succeeds, the client gives up or is shut down.
+    fn reconnect<G: Api>(
+        &mut self,
+        game: &G,
+        backoff: &Backoff,
+        url: &str,
+    ) -> Option<WebSocket<MaybeTlsStream<TcpStream>>> {
+        let mut attempt = 0;
+        loop {
+            let delay = backoff.delay(attempt)?;
+            attempt += 1;
+            let _ = self.events.send(Event::Reconnecting { attempt, delay });
+            let deadline = Instant::now() + delay;
+            while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+                if self.stop.load(Ordering::Acquire) {
+                    return None;
+                }
+                std::thread::sleep(left.min(POLL_INTERVAL));
+            }
+            while self.rx.try_recv().is_ok() {}
+            match open(url) {
+                Ok(ws) => {
+                    if let Err(err) = game.resume() {
+                        let _ = self.events.send(Event::Error(err.into()));
+                    }
+                    return Some(ws);
+                }
+                Err(err) => {
+                    let _ = self.events.send(Event::Error(err));
+                }
+            }
+        }
+    }
+}
+
+/// A running client. See [`connect`].
+///
+/// Dropping the client closes the connection and waits for the IO thread to exit.
+pub struct Client {
+    events: mpsc::Receiver<Event>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Client {
+    /// Wait for the next event. Returns `None` after the connection has been closed and all
+    /// events have been received.
+    pub fn next_event(&self) -> Option<Event> {
+        self.events.recv().ok()
+    }
+
+    /// Get the next event if there is one, without blocking.
+    pub fn try_next_event(&self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+
+    /// Send the remaining queued messages, close the connection and wait for the IO thread to
+    /// exit.
+    pub fn shutdown(self) {
+        drop(self);
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        borrow::Cow,
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+    };
+
+    use serde::Deserialize;
+
+    use crate::{
+        self as neuro_sama,
+        game::{Api, Game, Outbox, Session},
+    };
+
+    /// Test action
+    #[derive(Debug, schemars::JsonSchema, Deserialize)]
+    struct Jump;
+
+    #[derive(crate::derive::Actions, Debug)]
+    enum Action {
+        /// Jump
+        #[name = "jump"]
+        Jump(Jump),
+    }
+
+    struct TestGame {
+        tx: super::Sender,
+        session: Option<Session>,
+        reregistered: AtomicU32,
+    }
+
+    impl TestGame {
+        fn new(tx: super::Sender, session: Option<Session>) -> Arc<Self> {
+            Arc::new(Self {
+                tx,
+                session,
+                reregistered: AtomicU32::new(0),
+            })
+        }
+    }
+
+    impl Game for TestGame {
+        const NAME: &'static str = "Test";
+        type Actions<'a> = Action;
+        fn handle_action<'a>(
+            &self,
+            _action: Self::Actions<'a>,
+        ) -> Result<
+            Option<impl 'static + Into<Cow<'static, str>>>,
+            Option<impl 'static + Into<Cow<'static, str>>>,
+        > {
+            Ok::<_, Option<&str>>(Some("jumped"))
+        }
+        fn reregister_actions(&self) {
+            self.reregistered.fetch_add(1, Ordering::Relaxed);
+            self.register_actions::<Action>().unwrap();
+        }
+        fn session(&self) -> Option<Session> {
+            self.session.clone()
+        }
+        fn send_command(&self, message: tungstenite::Message) {
+            let _ = self.tx.send(message);
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut ws = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+            let startup = ws.read().unwrap();
+            ws.read().unwrap();
+            ws.send(tungstenite::Message::text(
+                r#"{"command":"action","data":{"id":"1","name":"jump"}}"#,
+            ))
+            .unwrap();
+            let result = ws.read().unwrap();
+            (startup, result)
+        });
+        let (tx, rx) = super::channel();
+        let client = super::connect(TestGame::new(tx, None), rx, &url).unwrap();
+        assert!(matches!(client.next_event(), Some(super::Event::Connected)));
+        let (startup, result) = server.join().unwrap();
+        assert_eq!(
+            startup.to_text().unwrap(),
+            r#"{"command":"startup","game":"Test"}"#
+        );
+        assert_eq!(
+            result.to_text().unwrap(),
+            r#"{"command":"action/result","data":{"id":"1","success":true,"message":"jumped"},"game":"Test"}"#
+        );
+        client.shutdown();
+    }
+
+    #[test]
+    fn test_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut ws = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+            let mut messages = vec![ws.read().unwrap(), ws.read().unwrap()];
+            ws.close(None).unwrap();
+            while ws.read().is_ok() {}
+            drop(ws);
+            let mut ws = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+            for _ in 0..3 {
+                messages.push(ws.read().unwrap());
+            }
+            messages
+        });
+        let (tx, rx) = super::channel();
+        let backoff =
+            super::Backoff::new().with_initial_delay(std::time::Duration::from_millis(200));
+        let session = Session::new().with_outbox(Outbox::new());
+        let game = TestGame::new(tx, Some(session.clone()));
+        let client = super::connect_with_reconnect(game.clone(), rx, &url, backoff).unwrap();
+        assert!(matches!(client.next_event(), Some(super::Event::Connected)));
+        assert!(matches!(
+            client.next_event(),
+            Some(super::Event::Disconnected)
+        ));
+        // queued by the outbox until the connection is back
+        assert!(!session.is_connected());
+        game.context("offline", true).unwrap();
+        assert_eq!(session.queued_commands().len(), 1);
+        assert!(matches!(
+            client.next_event(),
+            Some(super::Event::Reconnecting { attempt: 1, .. })
+        ));
+        assert!(matches!(client.next_event(), Some(super::Event::Connected)));
+        let messages = server.join().unwrap();
+        let messages = messages
+            .iter()
+            .map(|x| x.to_text().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(messages[0], messages[2]);
+        assert_eq!(messages[1], messages[3]);
+        assert!(messages[1].contains(r#""command":"actions/register""#));
+        assert_eq!(
+            messages[4],
+            r#"{"command":"context","data":{"message":"offline","silent":true},"game":"Test"}"#
+        );
+        client.shutdown();
+        // the actions were restored from the session
+        assert_eq!(game.reregistered.load(Ordering::Relaxed), 1);
+    }
+}
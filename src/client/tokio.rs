@@ -0,0 +1,344 @@
+//! An async client based on `tokio-tungstenite`.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use neuro_sama::client::{self, tokio::Sender};
+//!
+//! struct MyGame(Sender);
+//!
+//! impl neuro_sama::game::Game for MyGame {
+//!     // ...
+//!     fn send_command(&self, message: tungstenite::Message) {
+//!         let _ = self.0.send(message);
+//!     }
+//! }
+//!
+//! let (tx, rx) = client::tokio::channel();
+//! let mut client = client::tokio::run(MyGame(tx), rx, "ws://127.0.0.1:8000");
+//! while let Some(event) = client.next_event().await {
+//!     println!("{event:?}");
+//! }
+//! ```
+//!
+//! If your game implements [`AsyncGame`](crate::game::AsyncGame), use [`run_async`] instead.
+//! Since the futures returned by [`AsyncGame`](crate::game::AsyncGame) aren't required to be
+//! [`Send`], neither is [`AsyncClient::next_event`], so drive the client from the task that
+//! created it (or a [`LocalSet`](https://docs.rs/tokio/latest/tokio/task/struct.LocalSet.html))
+//! rather than spawning it onto a multi-threaded runtime.
+use std::{collections::VecDeque, time::Duration};
+
+use futures_util::{SinkExt, Stream, StreamExt};
+use tokio::{net::TcpStream, sync::mpsc};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use super::{Backoff, Error, Event, SendError};
+use crate::game::{Api, AsyncApi};
+
+/// Create a new channel for sending messages to the client. The [`Sender`] should be used in your
+/// [`Game::send_command`](crate::game::Game::send_command) implementation, and the [`Receiver`]
+/// should be passed to [`run`].
+pub fn channel() -> (Sender, Receiver) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (Sender(tx), Receiver(rx))
+}
+
+/// The sending half of the client's message queue. See [`channel`].
+#[derive(Clone, Debug)]
+pub struct Sender(mpsc::UnboundedSender<tungstenite::Message>);
+
+impl Sender {
+    /// Queue a message for sending. This fails if the client has been dropped.
+    pub fn send(&self, message: tungstenite::Message) -> Result<(), SendError> {
+        self.0.send(message).map_err(|err| SendError(err.0))
+    }
+}
+
+/// The receiving half of the client's message queue. See [`channel`].
+#[derive(Debug)]
+pub struct Receiver(mpsc::UnboundedReceiver<tungstenite::Message>);
+
+/// Create a client that connects to `url` and routes messages to and from `game`.
+///
+/// The client doesn't do anything until you start polling it for events via
+/// [`Client::next_event`] or [`Client::into_stream`]. Messages queued before the connection is
+/// established are discarded, since the `startup` command sent by [`Api::initialize`] would clear
+/// them anyway.
+pub fn run<G: Api>(game: G, outgoing: Receiver, url: impl Into<String>) -> Client<G> {
+    Client {
+        game,
+        rx: outgoing.0,
+        url: url.into(),
+        ws: None,
+        pending: VecDeque::new(),
+        reconnect: None,
+        attempt: 0,
+        delay: None,
+        connected: false,
+        done: false,
+    }
+}
+
+/// Create a client for a game that implements [`AsyncGame`](crate::game::AsyncGame). Apart
+/// from awaiting [`AsyncApi::handle_message`], this works exactly like [`run`]. Messages are
+/// still handled one at a time, so handlers never run concurrently.
+pub fn run_async<G: AsyncApi>(
+    game: G,
+    outgoing: Receiver,
+    url: impl Into<String>,
+) -> AsyncClient<G> {
+    AsyncClient {
+        game,
+        rx: outgoing.0,
+        url: url.into(),
+        ws: None,
+        pending: VecDeque::new(),
+        reconnect: None,
+        attempt: 0,
+        delay: None,
+        connected: false,
+        done: false,
+    }
+}
+
+/// A running client. See [`run`].
+pub struct Client<G: Api> {
+    game: G,
+    rx: mpsc::UnboundedReceiver<tungstenite::Message>,
+    url: String,
+    ws: Option<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    pending: VecDeque<Event>,
+    reconnect: Option<Backoff>,
+    attempt: u32,
+    delay: Option<Duration>,
+    connected: bool,
+    done: bool,
+}
+
+/// A running client for an [`AsyncGame`](crate::game::AsyncGame). See [`run_async`].
+pub struct AsyncClient<G: AsyncApi> {
+    game: G,
+    rx: mpsc::UnboundedReceiver<tungstenite::Message>,
+    url: String,
+    ws: Option<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    pending: VecDeque<Event>,
+    reconnect: Option<Backoff>,
+    attempt: u32,
+    delay: Option<Duration>,
+    connected: bool,
+    done: bool,
+}
+
+#[neuro_sama_derive::generic_asyncness(AsyncClient, AsyncApi)]
+impl<G: Api> Client<G> {
+    /// Reconnect with the specified backoff settings when the connection is lost (or can't be
+    /// established in the first place).
+    ///
+    /// After a reconnect, [`Api::resume`] is called. Messages sent while the client was
+    /// disconnected are discarded, unless they were queued by an [`Outbox`](crate::game::Outbox).
+    #[must_use]
+    pub fn with_reconnect(mut self, backoff: Backoff) -> Self {
+        self.reconnect = Some(backoff);
+        self
+    }
+
+    /// The game this client is running.
+    pub fn game(&self) -> &G {
+        &self.game
+    }
+
+    fn connection_lost(&mut self, err: Option<Error>) -> Option<Event> {
+        if let Some(session) = self.game.session() {
+            session.set_connected(false);
+        }
+        if self.ws.take().is_some() {
+            self.pending.push_back(Event::Disconnected);
+        }
+        match self
+            .reconnect
+            .as_ref()
+            .and_then(|backoff| backoff.delay(self.attempt))
+        {
+            Some(delay) => {
+                self.attempt += 1;
+                self.delay = Some(delay);
+                self.pending.push_back(Event::Reconnecting {
+                    attempt: self.attempt,
+                    delay,
+                });
+            }
+            None => {
+                if !self.connected && self.reconnect.is_none() {
+                    self.pending.push_back(Event::Disconnected);
+                }
+                self.done = true;
+            }
+        }
+        if let Some(err) = err {
+            self.pending.push_front(Event::Error(err));
+        }
+        self.pending.pop_front()
+    }
+
+    async fn connect(&mut self) -> Result<(), Error> {
+        let (ws, _) = tokio_tungstenite::connect_async(self.url.as_str()).await?;
+        self.attempt = 0;
+        while self.rx.try_recv().is_ok() {}
+        self.ws = Some(ws);
+        if self.connected {
+            self.game.resume()?;
+        } else {
+            self.connected = true;
+            self.game.initialize()?;
+        }
+        Ok(())
+    }
+
+    /// Drive the connection until the next event happens. Returns `None` after the connection has
+    /// been closed (and the client gave up reconnecting).
+    pub async fn next_event(&mut self) -> Option<Event> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        loop {
+            let Some(ws) = self.ws.as_mut() else {
+                if self.done {
+                    return None;
+                }
+                if let Some(delay) = self.delay.take() {
+                    tokio::time::sleep(delay).await;
+                }
+                return match self.connect().await {
+                    Ok(()) => Some(Event::Connected),
+                    Err(err) if self.ws.is_some() => {
+                        self.pending.push_back(Event::Connected);
+                        Some(Event::Error(err))
+                    }
+                    Err(err) => self.connection_lost(Some(err)),
+                };
+            };
+            let err = tokio::select! {
+                msg = self.rx.recv() => match msg {
+                    Some(msg) => match ws.send(msg).await {
+                        Ok(()) => continue,
+                        Err(err) => Some(err.into()),
+                    },
+                    None => {
+                        // all senders have been dropped, nothing else will be sent
+                        let _ = ws.close(None).await;
+                        self.reconnect = None;
+                        None
+                    }
+                },
+                msg = ws.next() => match msg {
+                    Some(Ok(msg)) => match self.game.handle_message(msg) {
+                        Ok(()) => continue,
+                        // this could happen if the message is malformed, unknown commands are passed
+                        // to `Game::unknown_command` instead
+                        Err(err) => return Some(Event::Error(err.into())),
+                    },
+                    Some(Err(err)) => Some(err.into()),
+                    None => None,
+                }
+            };
+            return self.connection_lost(err);
+        }
+    }
+
+    /// Convert the client into a [`Stream`] of events.
+    pub fn into_stream(self) -> impl Stream<Item = Event> {
+        futures_util::stream::unfold(self, |mut client| async move {
+            client.next_event().await.map(|event| (event, client))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{borrow::Cow, cell::Cell, net::TcpListener};
+
+    use serde::Deserialize;
+
+    use crate::{
+        self as neuro_sama,
+        game::{AsyncApi, AsyncGame},
+    };
+
+    /// Test action
+    #[derive(Debug, schemars::JsonSchema, Deserialize)]
+    struct Jump;
+
+    #[derive(crate::derive::Actions, Debug)]
+    enum Action {
+        /// Jump
+        #[name = "jump"]
+        Jump(Jump),
+    }
+
+    struct TestGame {
+        tx: super::Sender,
+        // not `Sync`, so the client's futures aren't `Send` either
+        jumps: Cell<u32>,
+    }
+
+    impl AsyncGame for TestGame {
+        const NAME: &'static str = "Test";
+        type Actions<'a> = Action;
+        async fn handle_action<'a>(
+            &self,
+            _action: Self::Actions<'a>,
+        ) -> Result<
+            Option<impl 'static + Into<Cow<'static, str>>>,
+            Option<impl 'static + Into<Cow<'static, str>>>,
+        > {
+            tokio::task::yield_now().await;
+            self.jumps.set(self.jumps.get() + 1);
+            Ok::<_, Option<&str>>(Some("jumped"))
+        }
+        fn reregister_actions(&self) {
+            self.register_actions::<Action>().unwrap();
+        }
+        fn send_command(&self, message: tungstenite::Message) {
+            let _ = self.tx.send(message);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_game() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut ws = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+            ws.read().unwrap();
+            ws.read().unwrap();
+            ws.send(tungstenite::Message::text(
+                r#"{"command":"action","data":{"id":"1","name":"jump"}}"#,
+            ))
+            .unwrap();
+            let result = ws.read().unwrap();
+            ws.close(None).unwrap();
+            while ws.read().is_ok() {}
+            result
+        });
+        let (tx, rx) = super::channel();
+        let game = TestGame {
+            tx,
+            jumps: Cell::new(0),
+        };
+        let mut client = super::run_async(game, rx, url);
+        assert!(matches!(
+            client.next_event().await,
+            Some(super::Event::Connected)
+        ));
+        assert!(matches!(
+            client.next_event().await,
+            Some(super::Event::Disconnected)
+        ));
+        assert!(client.next_event().await.is_none());
+        assert_eq!(
+            server.join().unwrap().to_text().unwrap(),
+            r#"{"command":"action/result","data":{"id":"1","success":true,"message":"jumped"},"game":"Test"}"#
+        );
+        assert_eq!(client.game().jumps.get(), 1);
+    }
+}
@@ -7,18 +7,54 @@
 //! instead - then you can use the [`ApiMut`] trait, which is exactly the same as [`Api`], except
 //! it takes a mutable reference, allowing you to mutate the object. You don't have to implement
 //! both.
+//!
+//! If handling an action requires awaiting something (e.g. a round-trip to the task that owns
+//! the game state), you can implement [`AsyncGame`] instead - its `handle_action` returns a
+//! future, and [`AsyncApi::handle_message`] awaits it before sending the action result. All other
+//! methods are the same as in [`Api`]. Messages must still be handled one at a time (i.e. don't
+//! call [`AsyncApi::handle_message`] again before the previous call has finished): the
+//! [`Session`] only keeps track of a single action that's currently being handled, so
+//! [`Api::defer_action_result`] and [`Api::current_action_id`] would refer to the wrong action.
+//! The futures aren't required to be [`Send`], so the game doesn't have to be [`Sync`] either.
 use std::{
     borrow::Cow,
     ops::{Deref, DerefMut},
+    time::{Duration, Instant},
 };
 
 use crate::schema::{self, ClientCommandContents, ServerCommand};
 
+mod action_error;
+mod coerce;
+#[doc(hidden)]
+pub mod constraints;
+mod execution;
+mod force;
 mod glue;
+mod limits;
+mod outbox;
+mod parse;
+mod session;
+mod two_phase;
+mod watchdog;
 
+pub use action_error::ActionError;
+pub use coerce::Coercion;
+pub use constraints::Constraints;
+pub use execution::ExecutionTemplate;
+pub use force::{ForceHandle, ForceStatus};
 pub use glue::{ActionMetadata, Actions};
-use schemars::schema::{InstanceType, Schema, SchemaObject, SingleOrVec};
+pub use limits::{LimitKind, LimitPolicy, Limits};
+pub use outbox::Outbox;
+pub use parse::ParseStage;
+use schemars::{
+    gen::SchemaSettings,
+    schema::{InstanceType, Metadata, Schema, SchemaObject, SingleOrVec, SubschemaValidation},
+};
+pub use session::{PendingAction, Session};
 use thiserror::Error;
+pub use two_phase::{ActionQueue, AsyncTwoPhaseGame, TwoPhaseGame, TwoPhaseGameMut};
+pub use watchdog::Watchdog;
 
 /// A trait to be implemented by your game to create an [`Api`] object.
 ///
@@ -32,12 +68,14 @@ use thiserror::Error;
 /// use serde::Deserialize;
 /// use neuro_sama::game::{Api, Game};
 ///
-/// // Note that the default schema for this will allow any integers from 0 to 255, which isn't
-/// // necessarily what we want. If you want to customize this, you will have to manually implement
-/// // the `JsonSchema` trait.
-/// #[derive(Debug, JsonSchema, Deserialize)]
+/// // Note that the default schema for `u8` allows any integers from 0 to 255, which isn't what we
+/// // want here. The `Constraints` derive narrows it down: the range is added to the schema, and
+/// // actions with coordinates outside of it are rejected (see the `Constraints` trait).
+/// #[derive(Debug, JsonSchema, Deserialize, neuro_sama::derive::Constraints)]
 /// struct Move {
+///     #[neuro(range = 0..=2)]
 ///     x: u8,
+///     #[neuro(range = 0..=2)]
 ///     y: u8,
 /// }
 ///
@@ -95,6 +133,7 @@ use thiserror::Error;
 ///     game.notify_message(message)?;
 /// }
 /// ```
+#[neuro_sama_derive::generic_asyncness(AsyncGame)]
 #[neuro_sama_derive::generic_mutability(GameMut)]
 pub trait Game: Sized {
     /// The game's display name, including any spaces and symbols (e.g. `"Buckshot Roulette"`).
@@ -124,6 +163,12 @@ pub trait Game: Sized {
     ///
     /// If you return `Err` on a forced action, Neuro will try again. If you don't want that, just
     /// return `Ok` with an error message.
+    ///
+    /// If you can't produce the result right away, call [`Api::defer_action_result`] and send
+    /// the result later via [`Api::send_action_result`].
+    ///
+    /// To validate actions here but execute them later from the game loop, see
+    /// [`TwoPhaseGame`].
     fn handle_action<'a>(
         &self,
         action: Self::Actions<'a>,
@@ -135,6 +180,33 @@ pub trait Game: Sized {
     /// Called when required by the game to reregister all available actions
     fn reregister_actions(&self);
 
+    /// The [`Session`] that tracks the state of this game's connection, if any. Sessions are
+    /// opt-in, see [`Session`] docs for more info.
+    fn session(&self) -> Option<Session> {
+        None
+    }
+
+    /// The settings used for generating action schemas in [`Api::register_actions`] (e.g.
+    /// [`SchemaSettings::draft2019_09`], or [`SchemaSettings::option_nullable`] to represent
+    /// `Option`s as `"nullable": true`). The default is [`SchemaSettings::draft07`].
+    ///
+    /// Since Neuro doesn't support references, they are inlined before the schema is sent
+    /// regardless of [`SchemaSettings::inline_subschemas`], except for recursive types, which
+    /// can't be inlined.
+    fn schema_settings(&self) -> SchemaSettings {
+        SchemaSettings::default()
+    }
+
+    /// The [`Limits`] on the size of messages sent to Neuro. There are no limits by default.
+    fn limits(&self) -> Limits {
+        Limits::default()
+    }
+
+    /// The wording of the context messages sent by [`Api::report_execution`].
+    fn execution_template(&self) -> ExecutionTemplate {
+        ExecutionTemplate::default()
+    }
+
     /// You should create or identify graceful shutdown points where the game can be closed gracefully after saving progress. You should store the latest received wants_shutdown value, and if it is true when a graceful shutdown point is reached, you should save the game and quit to main menu, then send back a shutdown ready message. Don't close the game entirely.
     ///
     /// # Note
@@ -153,10 +225,114 @@ pub trait Game: Sized {
     #[cfg(feature = "proposals")]
     fn immediate_shutdown(&self) {}
 
+    /// Called when the server sends a command this crate doesn't know about (e.g. one that was
+    /// added in a newer version of the API). By default, it's ignored.
+    fn unknown_command(&self, command: &str, data: Option<&serde_json::Value>) {
+        let _ = data;
+        log::debug!("ignoring unknown command `{command}`");
+    }
+
+    /// The message of the failure result sent when an incoming action is rejected before
+    /// reaching [`Game::handle_action`] (e.g. because its data doesn't match the action's type).
+    /// By default, this is a short plain-English description, override it to change the wording.
+    fn action_error_message(&self, error: &ActionError) -> String {
+        error.to_string()
+    }
+
+    /// Called before an action is handled if its data had to be coerced to match the registered
+    /// schema (see [`Session::with_coercion`]). By default, the coercions are logged.
+    fn action_coerced(&self, name: &str, coercions: &[Coercion]) {
+        for coercion in coercions {
+            log::info!("coerced the data of action `{name}`: {coercion}");
+        }
+    }
+
+    /// The stages of the pipeline that parses incoming action data, which are tried in order
+    /// until one of them succeeds. The default is just [`ParseStage::Json5`].
+    ///
+    /// If all stages fail, the action is rejected with the first stage's error.
+    fn parse_stages(&self) -> Vec<ParseStage> {
+        vec![ParseStage::Json5]
+    }
+
+    /// Called before an action is handled with the [`ParseStage`] that successfully parsed its
+    /// data (not called for actions without data). By default, this is logged.
+    fn action_parsed(&self, name: &str, stage: ParseStage) {
+        log::debug!("parsed the data of action `{name}` with the {stage} stage");
+    }
+
     /// Send a message to the WebSocket backend. If an error happens, you can handle it by
     /// attempting to reopen the connection and calling [`Api::initialize`] on the API after a
-    /// reconnect.
+    /// reconnect (the clients in the [`client`](crate::client) module can reconnect for you).
     fn send_command(&self, message: tungstenite::Message);
+
+    /// A fallible version of [`Game::send_command`], which is what the [`Api`] methods actually
+    /// call. Errors returned from here are propagated as [`Error::Transport`], along with the
+    /// command that couldn't be sent.
+    ///
+    /// By default, this calls [`Game::send_command`] and always succeeds. If you implement it,
+    /// you can implement [`Game::send_command`] by calling this and ignoring the error.
+    fn try_send_command(
+        &self,
+        message: tungstenite::Message,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.send_command(message);
+        Ok(())
+    }
+}
+
+/// Forward all [`Game`] methods apart from `handle_action` to `self.$deref()`, taking `self` by
+/// `&mut` if `mut` is passed.
+macro_rules! forward_game {
+    ($deref:ident $(, $mut:tt)?) => {
+        fn reregister_actions(&$($mut)? self) {
+            self.$deref().reregister_actions();
+        }
+        fn session(&$($mut)? self) -> Option<Session> {
+            self.$deref().session()
+        }
+        fn schema_settings(&$($mut)? self) -> SchemaSettings {
+            self.$deref().schema_settings()
+        }
+        fn limits(&$($mut)? self) -> Limits {
+            self.$deref().limits()
+        }
+        fn execution_template(&$($mut)? self) -> ExecutionTemplate {
+            self.$deref().execution_template()
+        }
+        #[cfg(feature = "proposals")]
+        fn graceful_shutdown_wanted(&$($mut)? self, wants_shutdown: bool) {
+            self.$deref().graceful_shutdown_wanted(wants_shutdown);
+        }
+        #[cfg(feature = "proposals")]
+        fn immediate_shutdown(&$($mut)? self) {
+            self.$deref().immediate_shutdown();
+        }
+        fn unknown_command(&$($mut)? self, command: &str, data: Option<&serde_json::Value>) {
+            self.$deref().unknown_command(command, data);
+        }
+        fn action_error_message(&$($mut)? self, error: &ActionError) -> String {
+            self.$deref().action_error_message(error)
+        }
+        fn action_coerced(&$($mut)? self, name: &str, coercions: &[Coercion]) {
+            self.$deref().action_coerced(name, coercions);
+        }
+        fn parse_stages(&$($mut)? self) -> Vec<ParseStage> {
+            self.$deref().parse_stages()
+        }
+        fn action_parsed(&$($mut)? self, name: &str, stage: ParseStage) {
+            self.$deref().action_parsed(name, stage);
+        }
+        fn send_command(&$($mut)? self, message: tungstenite::Message) {
+            self.$deref().send_command(message);
+        }
+        fn try_send_command(
+            &$($mut)? self,
+            message: tungstenite::Message,
+        ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            self.$deref().try_send_command(message)
+        }
+    };
 }
 
 impl<G: Game, T: Deref<Target = G>> Game for T {
@@ -175,20 +351,7 @@ impl<G: Game, T: Deref<Target = G>> Game for T {
             .map(|x| x.map(Into::into))
             .map_err(|x| x.map(Into::into))
     }
-    fn reregister_actions(&self) {
-        self.deref().reregister_actions();
-    }
-    #[cfg(feature = "proposals")]
-    fn graceful_shutdown_wanted(&self, wants_shutdown: bool) {
-        self.deref().graceful_shutdown_wanted(wants_shutdown);
-    }
-    #[cfg(feature = "proposals")]
-    fn immediate_shutdown(&self) {
-        self.deref().immediate_shutdown();
-    }
-    fn send_command(&self, message: tungstenite::Message) {
-        self.deref().send_command(message);
-    }
+    forward_game!(deref);
 }
 
 impl<G: GameMut, T: DerefMut<Target = G>> GameMut for T {
@@ -207,22 +370,35 @@ impl<G: GameMut, T: DerefMut<Target = G>> GameMut for T {
             .map(|x| x.map(Into::into))
             .map_err(|x| x.map(Into::into))
     }
-    fn reregister_actions(&mut self) {
-        self.deref_mut().reregister_actions();
-    }
-    #[cfg(feature = "proposals")]
-    fn graceful_shutdown_wanted(&mut self, wants_shutdown: bool) {
-        self.deref_mut().graceful_shutdown_wanted(wants_shutdown);
-    }
-    #[cfg(feature = "proposals")]
-    fn immediate_shutdown(&mut self) {
-        self.deref_mut().immediate_shutdown();
-    }
-    fn send_command(&mut self, message: tungstenite::Message) {
-        self.deref_mut().send_command(message);
-    }
+    forward_game!(deref_mut, mut);
 }
 
+// A blanket impl over `Deref` isn't possible here, since the returned future borrows `G` and
+// that requires `G` to outlive the borrow of `self`, which can't be expressed for arbitrary `T`.
+macro_rules! impl_async_game {
+    ($($ty:ty),*) => {$(
+        impl<G: AsyncGame> AsyncGame for $ty {
+            const NAME: &'static str = G::NAME;
+            type Actions<'a> = G::Actions<'a>;
+
+            fn handle_action<'a>(
+                &self,
+                action: Self::Actions<'a>,
+            ) -> impl std::future::Future<
+                Output = Result<
+                    Option<impl 'static + Into<Cow<'static, str>>>,
+                    Option<impl 'static + Into<Cow<'static, str>>>,
+                >,
+            > {
+                self.deref().handle_action(action)
+            }
+            forward_game!(deref);
+        }
+    )*};
+}
+
+impl_async_game!(&G, Box<G>, std::rc::Rc<G>, std::sync::Arc<G>);
+
 /// An error that occured somewhere while sending/receiving a message.
 #[non_exhaustive]
 #[derive(Debug, Error)]
@@ -234,6 +410,45 @@ pub enum Error {
         #[source]
         serde_json::Error,
     ),
+    /// Tried to force actions that aren't registered
+    #[error("tried to force actions that aren't registered: {}", .0.join(", "))]
+    UnregisteredActions(Vec<Cow<'static, str>>),
+    /// Tried to register an action with a name that doesn't follow the spec
+    #[error("invalid action name `{0}`: action names should be lowercase, with words separated by underscores or dashes")]
+    InvalidActionName(Cow<'static, str>),
+    /// Tried to register multiple actions with the same name at once
+    #[error("duplicate action names: {}", .0.join(", "))]
+    DuplicateActionNames(Vec<Cow<'static, str>>),
+    /// Tried to send a result for an action that isn't awaiting one
+    #[error("action `{0}` isn't awaiting a result (it was already answered or never deferred)")]
+    UnexpectedActionResult(String),
+    /// Tried to report the execution of an action that wasn't answered successfully (or isn't
+    /// remembered by the [`Session`] anymore, or there's no session), or whose execution was
+    /// already reported
+    #[error("action `{0}` has no execution to report (it wasn't answered successfully, was answered too long ago, or was already reported)")]
+    UnexpectedExecutionReport(String),
+    /// Failed to send a command
+    #[error("transport error: {source}")]
+    Transport {
+        /// The command that couldn't be sent
+        command: Box<schema::ClientCommandContents>,
+        /// The error returned by [`Game::try_send_command`]
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// A command exceeded one of the [`Limits`]
+    #[error("{kind}{} exceeds the limit ({len} > {max})", limits::of_action(.action.as_ref()))]
+    LimitExceeded {
+        /// The limit that was exceeded
+        kind: LimitKind,
+        /// The name of the action whose description or schema exceeded the limit (`None` for
+        /// limits that aren't about a single action)
+        action: Option<Cow<'static, str>>,
+        /// The actual length or size
+        len: usize,
+        /// The maximum length or size
+        max: usize,
+    },
 }
 
 /// A trait that has to be implemented by actions. It is automatically implemented when you create
@@ -250,74 +465,182 @@ pub trait Action: schemars::JsonSchema {
 }
 
 fn cleanup_action(action: &mut schema::Action) {
-    fn visit_schema(schema: &mut Schema) {
-        match schema {
-            Schema::Object(obj) => visit_schema_obj(obj),
-            Schema::Bool(_) => {}
-        }
-    }
-
-    fn visit_schema_obj(schema: &mut SchemaObject) {
-        if let Some(meta) = schema.metadata.as_mut() {
-            meta.description = None;
-            meta.title = None;
-        }
+    /// The direct subschemas of a schema object.
+    fn subschemas(schema: &mut SchemaObject) -> Vec<&mut Schema> {
+        let mut ret = Vec::new();
         if let Some(arr) = schema.array.as_mut() {
-            for x in &mut arr.items {
+            if let Some(x) = &mut arr.items {
                 match x {
-                    SingleOrVec::Single(schema) => visit_schema(schema),
-                    SingleOrVec::Vec(schemas) => {
-                        for schema in schemas {
-                            visit_schema(schema);
-                        }
-                    }
+                    SingleOrVec::Single(schema) => ret.push(&mut **schema),
+                    SingleOrVec::Vec(schemas) => ret.extend(schemas),
                 }
             }
-            for x in arr
-                .contains
-                .iter_mut()
-                .chain(arr.additional_items.iter_mut())
-            {
-                visit_schema(x);
-            }
+            ret.extend(
+                arr.contains
+                    .iter_mut()
+                    .chain(arr.additional_items.iter_mut())
+                    .map(|x| &mut **x),
+            );
         }
         if let Some(obj) = schema.object.as_mut() {
-            for schema in obj
-                .properties
-                .values_mut()
-                .chain(obj.pattern_properties.values_mut())
-                .chain(
-                    obj.additional_properties
-                        .iter_mut()
-                        .chain(obj.property_names.iter_mut())
-                        .map(|x| &mut **x),
-                )
-            {
-                visit_schema(schema);
-            }
+            ret.extend(
+                obj.properties
+                    .values_mut()
+                    .chain(obj.pattern_properties.values_mut())
+                    .chain(
+                        obj.additional_properties
+                            .iter_mut()
+                            .chain(obj.property_names.iter_mut())
+                            .map(|x| &mut **x),
+                    ),
+            );
         }
         if let Some(sub) = schema.subschemas.as_mut() {
-            for schema in sub
+            ret.extend(
+                sub.all_of
+                    .iter_mut()
+                    .chain(sub.any_of.iter_mut())
+                    .chain(sub.one_of.iter_mut())
+                    .flat_map(|x| x.iter_mut())
+                    .chain(
+                        sub.not
+                            .iter_mut()
+                            .chain(sub.if_schema.iter_mut())
+                            .chain(sub.then_schema.iter_mut())
+                            .chain(sub.else_schema.iter_mut())
+                            .map(|x| &mut **x),
+                    ),
+            );
+        }
+        ret
+    }
+
+    /// Inline references to `definitions`, except for recursive ones (those are added to `used`
+    /// instead, since the definitions they point to have to be kept).
+    fn resolve(
+        schema: &mut Schema,
+        definitions: &schemars::Map<String, Schema>,
+        stack: &mut Vec<String>,
+        used: &mut Vec<String>,
+    ) {
+        let Schema::Object(obj) = schema else {
+            return;
+        };
+        for schema in subschemas(obj) {
+            resolve(schema, definitions, stack, used);
+        }
+        // the prefix depends on `SchemaSettings::definitions_path`
+        let Some(name) = obj
+            .reference
+            .as_deref()
+            .and_then(|x| x.strip_prefix('#')?.rsplit_once('/'))
+            .map(|(_, name)| name.replace("~1", "/").replace("~0", "~"))
+        else {
+            return;
+        };
+        let Some(def) = definitions.get(&name) else {
+            return;
+        };
+        if stack.contains(&name) {
+            // the definitions are always serialized as `definitions`
+            obj.reference = Some(format!(
+                "#/definitions/{}",
+                name.replace('~', "~0").replace('/', "~1")
+            ));
+            if !used.contains(&name) {
+                used.push(name);
+            }
+            return;
+        }
+        let mut def = def.clone();
+        stack.push(name);
+        resolve(&mut def, definitions, stack, used);
+        stack.pop();
+        obj.reference = None;
+        if *obj
+            == (SchemaObject {
+                metadata: obj.metadata.clone(),
+                ..Default::default()
+            })
+        {
+            *schema = def;
+        } else {
+            obj.subschemas
+                .get_or_insert_with(Default::default)
                 .all_of
-                .iter_mut()
-                .chain(sub.any_of.iter_mut())
-                .chain(sub.one_of.iter_mut())
-                .flat_map(|x| x.iter_mut())
-                .chain(
-                    sub.not
-                        .iter_mut()
-                        .chain(sub.if_schema.iter_mut())
-                        .chain(sub.then_schema.iter_mut())
-                        .chain(sub.else_schema.iter_mut())
-                        .map(|x| &mut **x),
-                )
+                .get_or_insert_with(Vec::new)
+                .push(def);
+        }
+    }
+
+    fn visit_schema(schema: &mut Schema) {
+        let Schema::Object(obj) = schema else {
+            return;
+        };
+        visit_schema_obj(obj);
+        // `allOf` with a single schema is what schemars generates for documented fields with a
+        // reference, and is just noise after the reference is inlined
+        let only_all_of = match obj.subschemas.as_deref() {
+            Some(
+                sub @ SubschemaValidation {
+                    all_of: Some(all_of),
+                    ..
+                },
+            ) if all_of.len() == 1 => {
+                *sub == SubschemaValidation {
+                    all_of: Some(all_of.clone()),
+                    ..Default::default()
+                } && *obj
+                    == (SchemaObject {
+                        subschemas: obj.subschemas.clone(),
+                        ..Default::default()
+                    })
+            }
+            _ => false,
+        };
+        if only_all_of {
+            if let Some(inner) = obj
+                .subschemas
+                .as_mut()
+                .and_then(|x| x.all_of.as_mut())
+                .and_then(|x| x.pop())
             {
-                visit_schema(schema);
+                *schema = inner;
+            }
+        }
+    }
+
+    fn visit_schema_obj(schema: &mut SchemaObject) {
+        if let Some(meta) = schema.metadata.as_mut() {
+            meta.description = None;
+            meta.title = None;
+            if **meta == Metadata::default() {
+                schema.metadata = None;
             }
         }
+        for schema in subschemas(schema) {
+            visit_schema(schema);
+        }
     }
+
     action.schema.meta_schema = None;
-    visit_schema_obj(&mut action.schema.schema);
+    let definitions = std::mem::take(&mut action.schema.definitions);
+    let mut root = Schema::Object(std::mem::take(&mut action.schema.schema));
+    let mut used = Vec::new();
+    resolve(&mut root, &definitions, &mut Vec::new(), &mut used);
+    // recursive definitions can't be inlined, so keep them (and whatever they reference)
+    let mut i = 0;
+    while let Some(name) = used.get(i).cloned() {
+        if let Some(def) = definitions.get(&name) {
+            let mut def = def.clone();
+            resolve(&mut def, &definitions, &mut vec![name.clone()], &mut used);
+            visit_schema(&mut def);
+            action.schema.definitions.insert(name, def);
+        }
+        i += 1;
+    }
+    visit_schema(&mut root);
+    action.schema.schema = root.into_object();
     match &action.schema.schema.instance_type {
         Some(SingleOrVec::Single(x)) if **x == InstanceType::Null => {
             action.schema.schema.instance_type = None;
@@ -326,36 +649,233 @@ fn cleanup_action(action: &mut schema::Action) {
     }
 }
 
-fn send_ws_command<G: Game>(game: &G, cmd: schema::ClientCommandContents) -> Result<(), Error> {
-    let data = crate::to_string(&schema::ClientCommand {
-        command: cmd,
-        game: G::NAME.into(),
-    })?;
-    game.send_command(tungstenite::Message::text(data));
+/// An action that was successfully parsed.
+struct ParsedAction<A> {
+    action: A,
+    /// The stage that parsed the data, if there was any data.
+    stage: Option<ParseStage>,
+    coercions: Vec<Coercion>,
+}
+
+/// Deserialize an action, describing the problem if it fails.
+fn parse_action<'a, A: Actions<'a>>(
+    name: &str,
+    data: Option<&'a str>,
+    session: Option<&Session>,
+    stages: &[ParseStage],
+) -> Result<ParsedAction<A>, ActionError> {
+    let data = data.filter(|x| !x.trim().is_empty());
+    if let Some(session) = session {
+        if !session.is_registered(name) {
+            return Err(ActionError::UnknownAction {
+                name: name.to_owned(),
+                valid_actions: session.registered_names(),
+            });
+        }
+    }
+    let parsed = match data.map(|data| (data, parse::run(stages, data))) {
+        None => None,
+        Some((_, Ok(parsed))) => Some(parsed),
+        Some((data, Err(err))) => {
+            let mut data = data.to_owned();
+            data.retain(|x| !x.is_whitespace());
+            return if data.is_empty() || data == "{}" {
+                A::deserialize(name, serde::de::value::UnitDeserializer::new())
+                    .map(|action| ParsedAction {
+                        action,
+                        stage: None,
+                        coercions: Vec::new(),
+                    })
+                    .map_err(|_: serde_json::Error| err)
+            } else {
+                Err(err)
+            };
+        }
+    };
+    // parsed once for both coercion and validation, data that isn't valid JSON(5) is left to the
+    // deserializer
+    let mut value = session
+        .filter(|x| x.inspects_data())
+        .zip(parsed.as_ref())
+        .and_then(|(_, (_, text))| json5::from_str::<serde_json::Value>(text).ok());
+    let coercions = session
+        .zip(value.as_mut())
+        .map_or_else(Vec::new, |(session, value)| session.coerce(name, value));
+    #[cfg(feature = "validation")]
+    if let Some(errors) = session
+        .zip(value.as_ref())
+        .and_then(|(session, value)| session.validate(name, value))
+    {
+        return Err(ActionError::SchemaMismatch { errors });
+    }
+    let stage = parsed.as_ref().map(|(stage, _)| *stage);
+    let mut track = serde_path_to_error::Track::new();
+    let res = match (data.zip(parsed), value) {
+        // coerced data can't be borrowed from
+        (_, Some(value)) if !coercions.is_empty() => A::deserialize(
+            name,
+            serde_path_to_error::Deserializer::new(value, &mut track),
+        )
+        .map_err(|err| err.to_string()),
+        (None, _) => A::deserialize(
+            name,
+            serde_path_to_error::Deserializer::new(
+                serde::de::value::UnitDeserializer::<json5::Error>::new(),
+                &mut track,
+            ),
+        )
+        .map_err(|err| err.to_string()),
+        (Some((data, (ParseStage::Strict, _))), _) => A::deserialize(
+            name,
+            serde_path_to_error::Deserializer::new(
+                &mut serde_json::Deserializer::from_str(data),
+                &mut track,
+            ),
+        )
+        .map_err(|err| action_error::strip_location(&err)),
+        (Some((_, (ParseStage::Repair, text))), value) => {
+            // repaired data can't be borrowed from
+            let value = match value {
+                Some(value) => value,
+                None => json5::from_str::<serde_json::Value>(&text).map_err(ActionError::syntax)?,
+            };
+            A::deserialize(
+                name,
+                serde_path_to_error::Deserializer::new(value, &mut track),
+            )
+            .map_err(|err| err.to_string())
+        }
+        (Some((data, _)), _) => {
+            let mut de = json5::Deserializer::from_str(data).map_err(ActionError::syntax)?;
+            A::deserialize(
+                name,
+                serde_path_to_error::Deserializer::new(&mut de, &mut track),
+            )
+            .map_err(|err| err.to_string())
+        }
+    };
+    res.map(|action| ParsedAction {
+        action,
+        stage,
+        coercions,
+    })
+    .map_err(|message| {
+        let path = track.path();
+        ActionError::InvalidData {
+            path: action_error::format_path(&path),
+            expected: session
+                .and_then(|x| x.schema(name))
+                .and_then(|x| action_error::expected_at(&x, &path)),
+            message,
+        }
+    })
+}
+
+/// The id of an `action` command that couldn't be parsed, if it can be recovered.
+fn malformed_action_id(message: &[u8]) -> Option<String> {
+    #[derive(serde::Deserialize)]
+    struct Raw {
+        command: String,
+        data: Data,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Data {
+        id: String,
+    }
+
+    let Raw { command, data } = serde_json::from_slice(message).ok()?;
+    (command == "action").then_some(data.id)
+}
+
+/// Enforce the [`Limits`] on a command, then send the resulting commands.
+fn send_command_impl(
+    name: &'static str,
+    session: Option<Session>,
+    limits: Limits,
+    cmd: schema::ClientCommandContents,
+    mut send: impl FnMut(tungstenite::Message) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+) -> Result<(), Error> {
+    for cmd in limits.apply(cmd)? {
+        send_single_command(name, session.clone(), cmd, &mut send)?;
+    }
     Ok(())
 }
 
+/// Serialize and send a command, or queue it if there's an [`Outbox`] and the connection is down.
+fn send_single_command(
+    name: &'static str,
+    session: Option<Session>,
+    cmd: schema::ClientCommandContents,
+    send: impl FnOnce(tungstenite::Message) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+) -> Result<(), Error> {
+    let cmd = match &session {
+        Some(session) => session.enqueue(cmd),
+        None => Some(cmd),
+    };
+    let Some(cmd) = cmd else {
+        return Ok(());
+    };
+    let cmd = schema::ClientCommand {
+        command: cmd,
+        game: name.into(),
+    };
+    let data = crate::to_string(&cmd)?;
+    let Err(source) = send(tungstenite::Message::text(data)) else {
+        return Ok(());
+    };
+    let cmd = match session {
+        Some(session) => session.send_failed(cmd.command),
+        None => Some(cmd.command),
+    };
+    match cmd {
+        Some(command) => Err(Error::Transport {
+            command: Box::new(command),
+            source,
+        }),
+        None => {
+            log::warn!("failed to send a command, queueing it until reconnect: {source}");
+            Ok(())
+        }
+    }
+}
+
+fn send_ws_command<G: Game>(game: &G, cmd: schema::ClientCommandContents) -> Result<(), Error> {
+    send_command_impl(G::NAME, game.session(), game.limits(), cmd, |msg| {
+        game.try_send_command(msg)
+    })
+}
+
 fn send_ws_command_mut<G: GameMut>(
     game: &mut G,
     cmd: schema::ClientCommandContents,
 ) -> Result<(), Error> {
-    let data = crate::to_string(&schema::ClientCommand {
-        command: cmd,
-        game: G::NAME.into(),
-    })?;
-    game.send_command(tungstenite::Message::text(data));
-    Ok(())
+    send_command_impl(G::NAME, game.session(), game.limits(), cmd, |msg| {
+        game.try_send_command(msg)
+    })
+}
+
+fn send_ws_command_async<G: AsyncGame>(
+    game: &G,
+    cmd: schema::ClientCommandContents,
+) -> Result<(), Error> {
+    send_command_impl(G::NAME, game.session(), game.limits(), cmd, |msg| {
+        game.try_send_command(msg)
+    })
 }
 
 impl<T: Game> Api for T {}
 impl<T: GameMut> ApiMut for T {}
+impl<T: AsyncGame> AsyncApi for T {}
 
 /// A sealed trait implemented for all objects that implement [`Game`]. You can use these methods for
 /// talking to the Neuro API. Main points of interest are [`Api::initialize`] (which must be called first)
 /// and [`Api::handle_message`] for handling incoming WebSocket messages.
+#[neuro_sama_derive::generic_asyncness(AsyncApi, AsyncGame)]
 #[neuro_sama_derive::generic_mutability(ApiMut, GameMut)]
 pub trait Api: Game {
-    /// Reinitialize the API (sending the `startup` action and reregistering all actions).
+    /// Reinitialize the API (sending the `startup` action and reregistering all actions). This also
+    /// resets the [`Session`], if there is one.
     ///
     /// **This *must* be called before using any other method from [`Api`]**, and also whenever the
     /// WebSocket connection is reopened.
@@ -367,11 +887,54 @@ pub trait Api: Game {
     /// A previous version of this crate had a separate struct just for enforcing this being
     /// called, but not enforcing this at all seems to lead to a better API.
     fn initialize(&self) -> Result<(), Error> {
-        let ret = send_ws_command(self, ClientCommandContents::Startup);
-        if ret.is_ok() {
+        let outbox = self.session().and_then(|x| x.reset());
+        send_ws_command(self, ClientCommandContents::Startup)?;
+        if !outbox.as_ref().is_some_and(|x| x.restored) {
             self.reregister_actions();
         }
-        ret
+        let Some(outbox) = outbox else {
+            return Ok(());
+        };
+        if !outbox.register.is_empty() {
+            self.register_actions_raw(outbox.register)?;
+        }
+        for (message, silent) in outbox.context {
+            self.context(message, silent)?;
+        }
+        for cmd in outbox.other {
+            send_ws_command(self, cmd)?;
+        }
+        if let (Some((cmd, force)), Some(session)) = (outbox.force, self.session()) {
+            let stale = match &cmd {
+                ClientCommandContents::ForceActions { action_names, .. } => {
+                    action_names.iter().any(|x| !session.is_registered(x))
+                }
+                _ => false,
+            };
+            if stale {
+                if let Some(force) = force {
+                    force.finish(ForceStatus::Cancelled);
+                }
+            } else {
+                send_ws_command(self, cmd)?;
+                if let Some(force) = force {
+                    session.add_force(force);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reinitialize the API after the WebSocket connection has been reopened. This works like
+    /// [`Api::initialize`], except that if there is a [`Session`], the actions it had registered
+    /// are registered again instead of calling [`Game::reregister_actions`].
+    ///
+    /// The ready-made [`client`](crate::client)s call this after reconnecting.
+    fn resume(&self) -> Result<(), Error> {
+        if let Some(session) = self.session() {
+            session.restore();
+        }
+        self.initialize()
     }
 
     /// This message can be sent to let Neuro know about something that is happening in game.
@@ -427,15 +990,72 @@ pub trait Api: Game {
     /// api.unregister_actions::<Move>();
     /// ```
     fn register_actions<A: ActionMetadata>(&self) -> Result<(), Error> {
-        self.register_actions_raw(A::actions())
+        let actions = A::actions_with(&self.schema_settings());
+        self.register_actions_raw(actions)
     }
 
     /// Directly call `actions/register`. You should typically use [`Api::register_actions`] instead.
+    ///
+    /// If there is a [`Session`], actions that are already registered are skipped (and nothing
+    /// is sent if there are no actions left).
+    ///
+    /// In debug builds, the schemas are checked via [`schema::lint`], and the problems found are
+    /// logged as warnings.
+    ///
+    /// Fails with [`Error::InvalidActionName`] if an action name doesn't follow the spec (see
+    /// [`schema::is_valid_action_name`]), and with [`Error::DuplicateActionNames`] if multiple
+    /// actions have the same name.
     fn register_actions_raw(&self, mut actions: Vec<schema::Action>) -> Result<(), Error> {
+        let mut duplicates = Vec::new();
+        for (i, action) in actions.iter().enumerate() {
+            if !schema::is_valid_action_name(&action.name) {
+                return Err(Error::InvalidActionName(action.name.clone()));
+            }
+            if actions[..i].iter().any(|x| x.name == action.name)
+                && !duplicates.contains(&action.name)
+            {
+                duplicates.push(action.name.clone());
+            }
+        }
+        if !duplicates.is_empty() {
+            return Err(Error::DuplicateActionNames(duplicates));
+        }
         for action in &mut actions {
             cleanup_action(action);
         }
-        send_ws_command(self, ClientCommandContents::RegisterActions { actions })
+        let session = self.session();
+        if let Some(session) = &session {
+            actions = session.register(actions);
+            if actions.is_empty() {
+                return Ok(());
+            }
+        }
+        if cfg!(debug_assertions) {
+            // only lint the actions that weren't registered already, to avoid repeating warnings
+            for action in &actions {
+                for lint in schema::lint(&action.schema) {
+                    log::warn!("action `{}`: {lint}", action.name);
+                }
+            }
+        }
+        let names = match &session {
+            Some(_) => actions.iter().map(|x| x.name.clone()).collect(),
+            None => Vec::new(),
+        };
+        let res = send_ws_command(self, ClientCommandContents::RegisterActions { actions });
+        // the server doesn't know about the actions that couldn't be sent
+        match (&session, &res) {
+            (Some(session), Err(Error::LimitExceeded { .. })) => {
+                session.unregister(names);
+            }
+            (Some(session), Err(Error::Transport { command, .. })) => {
+                if let ClientCommandContents::RegisterActions { actions } = &**command {
+                    session.unregister(actions.iter().map(|x| x.name.clone()).collect());
+                }
+            }
+            _ => {}
+        }
+        res
     }
 
     /// Unregister actions. See [`Api::register_actions`] for example use.
@@ -444,71 +1064,145 @@ pub trait Api: Game {
     }
 
     /// Directly call `actions/unregister`. You should typically use [`Api::unregister_actions`] instead.
-    fn unregister_actions_raw(&self, action_names: Vec<Cow<'static, str>>) -> Result<(), Error> {
-        send_ws_command(
+    ///
+    /// If there is a [`Session`], actions that aren't registered are skipped (and nothing is sent
+    /// if there are no actions left).
+    fn unregister_actions_raw(
+        &self,
+        mut action_names: Vec<Cow<'static, str>>,
+    ) -> Result<(), Error> {
+        let session = self.session();
+        let mut previous = Vec::new();
+        if let Some(session) = &session {
+            previous = session.registered();
+            action_names = session.unregister(action_names);
+            if action_names.is_empty() {
+                return Ok(());
+            }
+        }
+        let res = send_ws_command(
             self,
             ClientCommandContents::UnregisterActions { action_names },
-        )
+        );
+        if let (Some(session), Err(Error::Transport { command, .. })) = (&session, &res) {
+            // the actions that couldn't be unregistered are still registered on the server
+            if let ClientCommandContents::UnregisterActions { action_names } = &**command {
+                previous.retain(|x| action_names.contains(&x.name));
+                session.register(previous);
+            }
+        }
+        res
+    }
+
+    /// Unregister all actions that are currently registered. This requires a [`Session`] (without
+    /// one, nothing is known to be registered, so nothing is unregistered).
+    fn unregister_all_actions(&self) -> Result<(), Error> {
+        let Some(action_names) = self.session().map(|x| x.registered_names()) else {
+            return Ok(());
+        };
+        self.unregister_actions_raw(action_names)
     }
 
     /// Handle a new websocket message. Note that this only handles `Text` and `Binary` messages,
     /// the rest are silently ignored.
+    ///
+    /// Actions that can't be handled (e.g. because their data is invalid, or because they aren't
+    /// registered according to the [`Session`]) are answered with a failure result describing
+    /// the problem, see [`ActionError`].
+    ///
+    /// Commands this crate doesn't know about are passed to [`Game::unknown_command`], while
+    /// known commands that are malformed fail with [`Error::Json`] (if it's an action and its id
+    /// can be recovered, it's answered with a failure result first).
     fn handle_message(&self, message: tungstenite::Message) -> Result<(), Error> {
-        let message = match message {
-            tungstenite::Message::Text(s) => serde_json::from_str(&s)?,
-            tungstenite::Message::Binary(b) => serde_json::from_slice(&b)?,
+        let message = match &message {
+            tungstenite::Message::Text(s) => s.as_bytes(),
+            tungstenite::Message::Binary(b) => b,
             _ => return Ok(()),
         };
-        let (id, res) = match message {
+        let message = match serde_json::from_slice(message) {
+            Ok(message) => message,
+            Err(err) => {
+                // otherwise Neuro would keep waiting for the result
+                if let Some(id) = malformed_action_id(message) {
+                    send_ws_command(
+                        self,
+                        ClientCommandContents::ActionResult {
+                            id,
+                            success: false,
+                            message: Some(format!("The action is malformed: {err}.").into()),
+                        },
+                    )?;
+                }
+                return Err(err.into());
+            }
+        };
+        let (id, name, res) = match message {
             ServerCommand::Action { id, name, data } => {
-                let res = data.as_ref().filter(|x| !x.trim().is_empty()).map_or_else(
-                    || {
-                        <Self::Actions<'_> as Actions>::deserialize(
-                            &name,
-                            serde::de::value::UnitDeserializer::new(),
-                        )
-                    },
-                    |data| match json5::Deserializer::from_str(data) {
-                        Ok(mut de) => <Self::Actions<'_> as Actions>::deserialize(&name, &mut de),
-                        Err(err) => {
-                            let mut data = data.clone();
-                            data.retain(|x| !x.is_whitespace());
-                            if data.is_empty() || data == "{}" {
-                                <Self::Actions<'_> as Actions>::deserialize(
-                                    &name,
-                                    serde::de::value::UnitDeserializer::new(),
-                                )
-                                .map_err(|_: serde_json::Error| err)
-                            } else {
-                                Err(err)
-                            }
-                        }
-                    },
+                let session = self.session();
+                if let Some(session) = session.as_ref().filter(|x| x.is_rejected(&name)) {
+                    // the action's force has been cancelled
+                    session.action_result(&name, false);
+                    return send_ws_command(
+                        self,
+                        ClientCommandContents::ActionResult {
+                            id,
+                            success: false,
+                            message: Some("This action is no longer available.".into()),
+                        },
+                    );
+                }
+                let res = parse_action::<Self::Actions<'_>>(
+                    &name,
+                    data.as_deref(),
+                    session.as_ref(),
+                    &self.parse_stages(),
                 );
                 let data = match res {
-                    Ok(data) => data,
+                    Ok(parsed) => {
+                        if let Some(stage) = parsed.stage {
+                            self.action_parsed(&name, stage);
+                        }
+                        if !parsed.coercions.is_empty() {
+                            self.action_coerced(&name, &parsed.coercions);
+                        }
+                        parsed.action
+                    }
                     Err(err) => {
+                        if let Some(session) = &session {
+                            session.action_result(&name, false);
+                        }
+                        let message = self.action_error_message(&err);
                         return send_ws_command(
                             self,
                             ClientCommandContents::ActionResult {
                                 id,
                                 success: false,
-                                message: Some(
-                                    ("Failed to deserialize Neuro-provided action data: "
-                                        .to_owned()
-                                        + &err.to_string())
-                                        .into(),
-                                ),
+                                message: Some(message.into()),
                             },
                         );
                     }
                 };
-                (id, self.handle_action(data))
+                if let Some(session) = &session {
+                    session.begin_action(&id, &name);
+                }
+                let res = self.handle_action(data);
+                if session.is_some_and(|session| session.end_action(Instant::now())) {
+                    // the result will be sent via `send_action_result`
+                    return Ok(());
+                }
+                (id, name, res)
             }
             #[cfg(feature = "proposals")]
             ServerCommand::ReregisterAllActions => {
-                self.reregister_actions();
-                return Ok(());
+                // if there's a session, we know exactly what should be registered
+                let Some(actions) = self.session().map(|x| x.registered()) else {
+                    self.reregister_actions();
+                    return Ok(());
+                };
+                if actions.is_empty() {
+                    return Ok(());
+                }
+                return send_ws_command(self, ClientCommandContents::RegisterActions { actions });
             }
             #[cfg(feature = "proposals")]
             ServerCommand::GracefulShutdown { wants_shutdown } => {
@@ -520,7 +1214,17 @@ pub trait Api: Game {
                 self.immediate_shutdown();
                 return Ok(());
             }
+            ServerCommand::Unknown { command, data } => {
+                self.unknown_command(&command, data.as_ref());
+                return Ok(());
+            }
         };
+        if let Some(session) = self.session() {
+            session.action_result(&name, res.is_ok());
+            if res.is_ok() {
+                session.answered(&id, &name);
+            }
+        }
         let res = match res {
             Ok(msg) => ClientCommandContents::ActionResult {
                 id,
@@ -536,6 +1240,152 @@ pub trait Api: Game {
         send_ws_command(self, res)
     }
 
+    /// Defer the result of the action that's currently being handled. This must be called from
+    /// [`Game::handle_action`], which will then have its return value ignored - instead, you must
+    /// send the result later via [`Api::send_action_result`].
+    ///
+    /// This requires a [`Session`] (it returns `None` without one, or when called outside of
+    /// [`Game::handle_action`]). The session keeps track of the deferred actions, and will log a
+    /// warning about the ones that never get a result.
+    ///
+    /// When using [`AsyncApi`], this doesn't work if multiple messages are handled concurrently
+    /// (see the [module docs](self)).
+    fn defer_action_result(&self) -> Option<PendingAction> {
+        self.session()?.defer()
+    }
+
+    /// Send the result of an action deferred using [`Api::defer_action_result`]. Each deferred
+    /// action must be answered exactly once, answering an action that isn't awaiting a result
+    /// fails with [`Error::UnexpectedActionResult`].
+    ///
+    /// # Parameters
+    ///
+    /// - `id` - the id of the action, see [`PendingAction::id`].
+    /// - `success` - whether the action was successful. *If this is `false` and this action is part of an actions force, the whole actions force will be immediately retried by Neuro.*
+    /// - `message` - a plaintext message that describes what happened when the action was executed. **This information will be directly received by Neuro.**
+    fn send_action_result(
+        &self,
+        id: impl Into<String>,
+        success: bool,
+        message: Option<Cow<'static, str>>,
+    ) -> Result<(), Error> {
+        let id = id.into();
+        let Some(session) = self.session() else {
+            return Err(Error::UnexpectedActionResult(id));
+        };
+        let Some(name) = session.resolve(&id) else {
+            return Err(Error::UnexpectedActionResult(id));
+        };
+        session.action_result(&name, success);
+        if success {
+            session.answered(&id, &name);
+        }
+        send_ws_command(
+            self,
+            ClientCommandContents::ActionResult {
+                id,
+                success,
+                message,
+            },
+        )
+    }
+
+    /// The id of the action that's currently being handled. This must be called from
+    /// [`Game::handle_action`], and requires a [`Session`] (it returns `None` otherwise). Keep it
+    /// around if you want to report the action's execution later via
+    /// [`Api::report_execution`].
+    ///
+    /// When using [`AsyncApi`], this doesn't work if multiple messages are handled concurrently
+    /// (see the [module docs](self)).
+    fn current_action_id(&self) -> Option<String> {
+        self.session()?.current_id()
+    }
+
+    /// Let Neuro know how the execution of an action went after it was answered with a successful
+    /// result (e.g. if the move she chose turned out to be blocked in-game), so that her picture
+    /// of the game stays correct. This sends a context message worded according to
+    /// [`Game::execution_template`].
+    ///
+    /// This requires a [`Session`], which remembers the recent successfully answered actions.
+    /// Each of them can be reported once, reporting any other action fails with
+    /// [`Error::UnexpectedExecutionReport`]. In particular, this always fails without a session,
+    /// and only the last 64 successfully answered actions can be reported (so report the
+    /// execution as soon as you know how it went).
+    ///
+    /// # Parameters
+    ///
+    /// - `id` - the id of the action, see [`Api::current_action_id`] or [`PendingAction::id`].
+    /// - `success` - whether the action was executed successfully.
+    /// - `message` - a plaintext message that describes what happened. **This information will be directly received by Neuro.**
+    fn report_execution(
+        &self,
+        id: &str,
+        success: bool,
+        message: Option<Cow<'static, str>>,
+    ) -> Result<(), Error> {
+        let Some(name) = self.session().and_then(|x| x.executed(id)) else {
+            return Err(Error::UnexpectedExecutionReport(id.to_owned()));
+        };
+        let template = self.execution_template();
+        let message = template.render(id, &name, success, message.as_deref().unwrap_or(""));
+        send_ws_command(
+            self,
+            ClientCommandContents::Context {
+                message: message.into(),
+                silent: template.silent(),
+            },
+        )
+    }
+
+    /// Cancel an action force: the forced actions are unregistered, and if there is a
+    /// [`Session`], Neuro's attempts to execute them (until they're registered again) are
+    /// rejected without calling [`Game::handle_action`]. Does nothing if the force isn't
+    /// [`ForceStatus::Pending`].
+    fn cancel_force(&self, force: &ForceHandle) -> Result<(), Error> {
+        if !force.finish(ForceStatus::Cancelled) {
+            return Ok(());
+        }
+        if let Some(session) = self.session() {
+            session.remove_force(force);
+        }
+        self.unregister_actions_raw(force.action_names().to_vec())
+    }
+
+    /// Handle timeouts. This requires a [`Session`], and should be called periodically (e.g.
+    /// every frame).
+    ///
+    /// When an action force times out, it's cancelled the same way as with
+    /// [`Api::cancel_force`], and then its fallback is called. If the session has a [`Watchdog`],
+    /// slow deferred actions are reported, and the ones past the deadline are answered with a
+    /// failure result.
+    fn tick(&self) -> Result<(), Error> {
+        let Some(session) = self.session() else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let mut ret = Ok(());
+        for (id, name) in session.overdue(now) {
+            session.action_result(&name, false);
+            let res = send_ws_command(
+                self,
+                ClientCommandContents::ActionResult {
+                    id,
+                    success: false,
+                    message: Some("The game took too long to handle this action.".into()),
+                },
+            );
+            ret = ret.and(res);
+        }
+        for (force, fallback) in session.timed_out(now) {
+            let res = self.unregister_actions_raw(force.action_names().to_vec());
+            if let Some(fallback) = fallback {
+                fallback();
+            }
+            ret = ret.and(res);
+        }
+        ret
+    }
+
     /// Tell Neuro to execute one of the listed actions as soon as possible. Note that this might take a bit if she is already talking.
     ///
     /// # Parameters
@@ -551,8 +1401,10 @@ pub trait Api: Game {
     fn force_actions<T: ActionMetadata>(
         &self,
         query: Cow<'static, str>,
-    ) -> ForceActionsBuilder<Self> {
-        self.force_actions_raw(query, T::names())
+    ) -> ForceActionsBuilder<'_, Self> {
+        let mut ret = self.force_actions_raw(query, T::names());
+        ret.actions = Some(T::actions_with);
+        ret
     }
 
     /// A version of [`Api::force_actions`] that uses raw action names instead of type parameters.
@@ -561,13 +1413,16 @@ pub trait Api: Game {
         &self,
         query: Cow<'static, str>,
         action_names: Vec<Cow<'static, str>>,
-    ) -> ForceActionsBuilder<Self> {
+    ) -> ForceActionsBuilder<'_, Self> {
         ForceActionsBuilder {
             api: self,
             state: None,
             query,
             ephemeral_context: None,
             action_names,
+            actions: None,
+            auto_register: false,
+            timeout: None,
         }
     }
 }
@@ -579,6 +1434,9 @@ pub struct ForceActionsBuilder<'a, G: Api> {
     query: Cow<'static, str>,
     ephemeral_context: Option<bool>,
     action_names: Vec<Cow<'static, str>>,
+    actions: Option<fn(&SchemaSettings) -> Vec<schema::Action>>,
+    auto_register: bool,
+    timeout: Option<(Duration, force::Fallback)>,
 }
 
 /// A mutable version of [`ForceActionsBuilder`]. See [`ForceActionsBuilder`] docs for more info.
@@ -588,8 +1446,24 @@ pub struct ForceActionsBuilderMut<'a, G: ApiMut> {
     query: Cow<'static, str>,
     ephemeral_context: Option<bool>,
     action_names: Vec<Cow<'static, str>>,
+    actions: Option<fn(&SchemaSettings) -> Vec<schema::Action>>,
+    auto_register: bool,
+    timeout: Option<(Duration, force::Fallback)>,
 }
 
+/// An async version of [`ForceActionsBuilder`]. See [`ForceActionsBuilder`] docs for more info.
+pub struct ForceActionsBuilderAsync<'a, G: AsyncApi> {
+    api: &'a G,
+    state: Option<Cow<'static, str>>,
+    query: Cow<'static, str>,
+    ephemeral_context: Option<bool>,
+    action_names: Vec<Cow<'static, str>>,
+    actions: Option<fn(&SchemaSettings) -> Vec<schema::Action>>,
+    auto_register: bool,
+    timeout: Option<(Duration, force::Fallback)>,
+}
+
+#[neuro_sama_derive::generic_asyncness(ForceActionsBuilderAsync, AsyncApi)]
 #[neuro_sama_derive::generic_mutability(ForceActionsBuilderMut, ApiMut)]
 impl<'a, G: Api> ForceActionsBuilder<'a, G> {
     /// If `false`, the context provided in the `state` and `query` parameters will be remembered by Neuro after the actions force is compelted. If `true`, Neuro will only remember it for the duration of the actions force.
@@ -606,8 +1480,57 @@ impl<'a, G: Api> ForceActionsBuilder<'a, G> {
         self
     }
 
-    /// Send the WebSocket message to the server.
-    pub fn send(self) -> Result<(), Error> {
+    /// If some of the forced actions aren't registered, register them before sending the
+    /// message instead of failing. This only works with [`Api::force_actions`], since
+    /// [`Api::force_actions_raw`] doesn't know the actions' schemas.
+    #[must_use]
+    pub fn with_auto_register(mut self, auto_register: bool) -> Self {
+        self.auto_register = auto_register;
+        self
+    }
+
+    /// Give Neuro a limited amount of time to complete the force. If none of the forced actions
+    /// succeed in time, the force is cancelled (see [`Api::cancel_force`]) and `fallback` is
+    /// called, e.g. to pick a default action.
+    ///
+    /// Timeouts are handled by [`Api::tick`], so they require a [`Session`].
+    #[must_use]
+    pub fn with_timeout(
+        mut self,
+        timeout: Duration,
+        fallback: impl 'static + Send + FnOnce(),
+    ) -> Self {
+        self.timeout = Some((timeout, Box::new(fallback)));
+        self
+    }
+
+    /// Send the WebSocket message to the server, returning a handle that can be used for tracking
+    /// the force.
+    ///
+    /// If there is a [`Session`], this fails with [`Error::UnregisteredActions`] if some of the
+    /// forced actions aren't currently registered (unless [`Self::with_auto_register`] was used).
+    pub fn send(self) -> Result<ForceHandle, Error> {
+        if let Some(session) = self.api.session() {
+            let mut missing = self
+                .action_names
+                .iter()
+                .filter(|name| !session.is_registered(name))
+                .cloned()
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                let mut actions = match self.actions.filter(|_| self.auto_register) {
+                    Some(actions) => actions(&self.api.schema_settings()),
+                    None => Vec::new(),
+                };
+                actions.retain(|action| missing.contains(&action.name));
+                missing.retain(|name| !actions.iter().any(|action| &action.name == name));
+                if !missing.is_empty() {
+                    return Err(Error::UnregisteredActions(missing));
+                }
+                self.api.register_actions_raw(actions)?;
+            }
+        }
+        let force = ForceHandle::new(self.action_names.clone(), self.timeout);
         send_ws_command(
             self.api,
             schema::ClientCommandContents::ForceActions {
@@ -616,17 +1539,29 @@ impl<'a, G: Api> ForceActionsBuilder<'a, G> {
                 ephemeral_context: self.ephemeral_context,
                 action_names: self.action_names,
             },
-        )
+        )?;
+        if let Some(session) = self.api.session() {
+            session.add_force(force.clone());
+        }
+        Ok(force)
     }
 }
 
 #[cfg(test)]
 mod test {
+    use std::{
+        borrow::Cow,
+        cell::{Cell, RefCell},
+    };
+
     use serde::Deserialize;
 
     use crate::{
         self as neuro_sama,
-        game::{cleanup_action, ActionMetadata},
+        game::{
+            cleanup_action, ActionMetadata, Api, AsyncApi, AsyncGame, Game, Limits, ParseStage,
+            PendingAction, Session,
+        },
     };
 
     /// Move action
@@ -650,6 +1585,894 @@ mod test {
         Shoot(Shoot),
     }
 
+    #[derive(Default)]
+    struct TestGame {
+        sent: RefCell<Vec<String>>,
+        session: Option<Session>,
+        defer: bool,
+        deferred: RefCell<Vec<PendingAction>>,
+        offline: Cell<bool>,
+        limits: Limits,
+        parse_stages: Vec<ParseStage>,
+    }
+
+    impl TestGame {
+        fn with_session() -> Self {
+            Self {
+                session: Some(Session::new()),
+                ..Self::default()
+            }
+        }
+
+        fn take_sent(&self) -> Vec<String> {
+            std::mem::take(&mut *self.sent.borrow_mut())
+        }
+    }
+
+    impl Game for TestGame {
+        const NAME: &'static str = "Test";
+        type Actions<'a> = Action;
+        fn handle_action<'a>(
+            &self,
+            action: Self::Actions<'a>,
+        ) -> Result<
+            Option<impl 'static + Into<Cow<'static, str>>>,
+            Option<impl 'static + Into<Cow<'static, str>>>,
+        > {
+            if self.defer {
+                let pending = self.defer_action_result().unwrap();
+                self.deferred.borrow_mut().push(pending);
+            }
+            match action {
+                Action::Move(_) => Ok(Some("moved")),
+                Action::Shoot(_) => Err(Some("no ammo")),
+            }
+        }
+        fn reregister_actions(&self) {
+            self.register_actions::<Action>().unwrap();
+        }
+        fn session(&self) -> Option<Session> {
+            self.session.clone()
+        }
+        fn limits(&self) -> Limits {
+            self.limits.clone()
+        }
+        fn parse_stages(&self) -> Vec<ParseStage> {
+            self.parse_stages.clone()
+        }
+        fn send_command(&self, message: tungstenite::Message) {
+            self.sent.borrow_mut().push(message.into_text().unwrap());
+        }
+        fn try_send_command(
+            &self,
+            message: tungstenite::Message,
+        ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            if self.offline.get() {
+                return Err("offline".into());
+            }
+            self.send_command(message);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_session() {
+        let game = TestGame::with_session();
+        game.initialize().unwrap();
+        assert_eq!(game.take_sent().len(), 2);
+        let session = game.session().unwrap();
+        assert!(session.is_registered("move"));
+        assert!(session.is_registered("shoot"));
+        game.register_actions::<Move>().unwrap();
+        assert!(game.take_sent().is_empty());
+        game.unregister_actions::<Move>().unwrap();
+        game.unregister_actions::<Move>().unwrap();
+        assert_eq!(
+            game.take_sent(),
+            [r#"{"command":"actions/unregister","data":{"action_names":["move"]},"game":"Test"}"#]
+        );
+        assert!(!session.is_registered("move"));
+        game.unregister_all_actions().unwrap();
+        assert_eq!(
+            game.take_sent(),
+            [r#"{"command":"actions/unregister","data":{"action_names":["shoot"]},"game":"Test"}"#]
+        );
+        assert!(session.registered().is_empty());
+        game.initialize().unwrap();
+        assert_eq!(session.registered_names(), ["move", "shoot"]);
+    }
+
+    #[test]
+    fn test_force_unregistered() {
+        let game = TestGame::with_session();
+        game.initialize().unwrap();
+        game.unregister_actions::<Move>().unwrap();
+        game.take_sent();
+        let err = game
+            .force_actions_raw("move".into(), vec!["move".into(), "jump".into()])
+            .send()
+            .unwrap_err();
+        assert!(
+            matches!(err, super::Error::UnregisteredActions(ref names) if names == &["move", "jump"])
+        );
+        assert!(game.force_actions::<Move>("move".into()).send().is_err());
+        assert!(game.take_sent().is_empty());
+        game.force_actions::<Action>("move".into())
+            .with_auto_register(true)
+            .send()
+            .unwrap();
+        let sent = game.take_sent();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0].contains(r#""command":"actions/register""#));
+        assert!(sent[1].contains(r#""command":"actions/force""#));
+        assert!(game.session().unwrap().is_registered("move"));
+    }
+
+    #[test]
+    fn test_force_handle() {
+        use std::sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        };
+
+        use super::ForceStatus;
+
+        let action = |game: &TestGame, name: &str| {
+            game.handle_message(tungstenite::Message::text(format!(
+                r#"{{"command":"action","data":{{"id":"1","name":"{name}","data":"{{\"x\":1,\"y\":2}}"}}}}"#
+            )))
+            .unwrap();
+            game.take_sent()
+        };
+        let game = TestGame::with_session();
+        game.initialize().unwrap();
+        let force = game
+            .force_actions::<Action>("do something".into())
+            .send()
+            .unwrap();
+        action(&game, "shoot");
+        assert_eq!(force.failed_attempts(), 1);
+        assert_eq!(force.status(), ForceStatus::Pending);
+        action(&game, "move");
+        assert!(force.is_completed());
+        assert!(game.session().unwrap().pending_forces().is_empty());
+
+        let force = game.force_actions::<Move>("move".into()).send().unwrap();
+        game.take_sent();
+        game.cancel_force(&force).unwrap();
+        assert_eq!(force.status(), ForceStatus::Cancelled);
+        assert_eq!(
+            game.take_sent(),
+            [r#"{"command":"actions/unregister","data":{"action_names":["move"]},"game":"Test"}"#]
+        );
+        let sent = action(&game, "move");
+        assert!(sent[0].contains(r#""success":false"#));
+        assert!(sent[0].contains("no longer available"));
+
+        let called = Arc::new(AtomicBool::new(false));
+        let force = game
+            .force_actions::<Move>("move".into())
+            .with_auto_register(true)
+            .with_timeout(std::time::Duration::ZERO, {
+                let called = called.clone();
+                move || called.store(true, Ordering::Relaxed)
+            })
+            .send()
+            .unwrap();
+        game.take_sent();
+        assert!(action(&game, "move")[0].contains(r#""success":true"#));
+        assert!(force.is_completed());
+        game.tick().unwrap();
+        assert!(!called.load(Ordering::Relaxed));
+        let force = game
+            .force_actions::<Move>("move".into())
+            .with_timeout(std::time::Duration::ZERO, {
+                let called = called.clone();
+                move || called.store(true, Ordering::Relaxed)
+            })
+            .send()
+            .unwrap();
+        game.take_sent();
+        game.tick().unwrap();
+        assert_eq!(force.status(), ForceStatus::TimedOut);
+        assert!(called.load(Ordering::Relaxed));
+        assert_eq!(game.take_sent().len(), 1);
+    }
+
+    #[test]
+    fn test_transport_error() {
+        let game = TestGame {
+            offline: Cell::new(true),
+            ..TestGame::default()
+        };
+        let err = game.context("hello", true).unwrap_err();
+        assert!(matches!(
+            err,
+            super::Error::Transport { ref command, .. }
+                if matches!(**command, crate::schema::ClientCommandContents::Context { .. })
+        ));
+        assert_eq!(err.to_string(), "transport error: offline");
+        assert!(game.force_actions::<Move>("move".into()).send().is_err());
+        assert!(game.take_sent().is_empty());
+
+        // the session isn't changed by commands that couldn't be sent
+        let game = TestGame {
+            session: Some(Session::new()),
+            offline: Cell::new(true),
+            ..TestGame::default()
+        };
+        let session = game.session().unwrap();
+        assert!(game.register_actions::<Action>().is_err());
+        assert!(session.registered().is_empty());
+        game.offline.set(false);
+        game.register_actions::<Action>().unwrap();
+        game.offline.set(true);
+        assert!(game.unregister_actions::<Move>().is_err());
+        assert!(session.is_registered("move"));
+        assert!(session.is_registered("shoot"));
+    }
+
+    #[test]
+    fn test_limits() {
+        use super::{LimitKind, LimitPolicy};
+        let limits = Limits::new()
+            .with_context_len(10)
+            .with_state_len(10)
+            .with_actions_per_registration(1)
+            .with_truncation_marker("...");
+        let game = TestGame {
+            session: Some(Session::new()),
+            limits: limits.clone(),
+            ..TestGame::default()
+        };
+        game.context("hello world", true).unwrap();
+        game.register_actions::<Action>().unwrap();
+        game.force_actions::<Move>("move".into())
+            .with_state("ÿÿÿÿÿÿÿÿÿÿÿ")
+            .send()
+            .unwrap();
+        let sent = game.take_sent();
+        assert_eq!(sent.len(), 4);
+        assert!(sent[0].contains(r#""message":"hello w...""#));
+        assert!(sent[1].contains(r#""name":"move""#) && !sent[1].contains(r#""shoot""#));
+        assert!(sent[2].contains(r#""name":"shoot""#));
+        assert!(sent[3].contains(r#""state":"ÿÿÿÿÿÿÿ...""#));
+
+        let game = TestGame {
+            session: Some(Session::new()),
+            limits: limits.with_policy(LimitPolicy::Reject),
+            ..TestGame::default()
+        };
+        assert!(matches!(
+            game.context("hello world", true),
+            Err(super::Error::LimitExceeded {
+                kind: LimitKind::Context,
+                action: None,
+                len: 11,
+                max: 10,
+            })
+        ));
+        assert!(game.register_actions::<Action>().is_err());
+        assert!(game.take_sent().is_empty());
+        // rejected actions aren't considered registered
+        assert!(game.session.as_ref().unwrap().registered().is_empty());
+
+        // the marker is cut down if it doesn't fit
+        let game = TestGame {
+            limits: Limits::new()
+                .with_context_len(2)
+                .with_truncation_marker("..."),
+            ..TestGame::default()
+        };
+        game.context("hello", true).unwrap();
+        assert!(game.take_sent()[0].contains(r#""message":"..""#));
+
+        // schemas are measured as they're sent, and the action is named in the error
+        let mut actions = <Move as ActionMetadata>::actions();
+        cleanup_action(&mut actions[0]);
+        let size = crate::to_string(&actions[0].schema).unwrap().len();
+        let game = TestGame {
+            limits: Limits::new()
+                .with_schema_size(size - 1)
+                .with_policy(LimitPolicy::Reject),
+            ..TestGame::default()
+        };
+        let err = game.register_actions::<Move>().unwrap_err();
+        assert!(matches!(
+            err,
+            super::Error::LimitExceeded {
+                kind: LimitKind::Schema,
+                action: Some(ref name),
+                ..
+            } if name == "move"
+        ));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "action schema size of action `move` exceeds the limit ({size} > {})",
+                size - 1
+            )
+        );
+        let game = TestGame {
+            limits: Limits::new()
+                .with_schema_size(size)
+                .with_policy(LimitPolicy::Reject),
+            ..TestGame::default()
+        };
+        game.register_actions::<Move>().unwrap();
+
+        // the server still has the actions when re-registering them is rejected
+        #[cfg(feature = "proposals")]
+        {
+            let game = TestGame {
+                session: Some(Session::new()),
+                limits: Limits::new()
+                    .with_actions_per_registration(1)
+                    .with_policy(LimitPolicy::Reject),
+                ..TestGame::default()
+            };
+            game.register_actions::<Move>().unwrap();
+            game.register_actions::<Shoot>().unwrap();
+            assert!(game
+                .handle_message(tungstenite::Message::text(
+                    r#"{"command":"actions/reregister_all"}"#
+                ))
+                .is_err());
+            assert_eq!(game.session().unwrap().registered().len(), 2);
+        }
+
+        let game = TestGame {
+            limits: Limits::new()
+                .with_schema_size(10)
+                .with_policy(LimitPolicy::Warn),
+            ..TestGame::default()
+        };
+        game.register_actions::<Action>().unwrap();
+        assert_eq!(game.take_sent().len(), 1);
+    }
+
+    #[test]
+    fn test_outbox() {
+        use super::{ForceStatus, Outbox};
+
+        let game = TestGame {
+            session: Some(
+                Session::new().with_outbox(Outbox::new().with_collapse_silent_context(true)),
+            ),
+            ..TestGame::default()
+        };
+        game.initialize().unwrap();
+        game.take_sent();
+        game.offline.set(true);
+        game.context("z", false).unwrap();
+        let session = game.session().unwrap();
+        assert!(!session.is_connected());
+        game.context("a", true).unwrap();
+        game.context("b", true).unwrap();
+        game.context("c", false).unwrap();
+        game.unregister_actions::<Move>().unwrap();
+        game.register_actions::<Move>().unwrap();
+        let force1 = game.force_actions::<Move>("1".into()).send().unwrap();
+        let force2 = game.force_actions::<Action>("2".into()).send().unwrap();
+        assert_eq!(session.queued_commands().len(), 8);
+        assert!(game.take_sent().is_empty());
+        game.offline.set(false);
+        game.initialize().unwrap();
+        assert_eq!(force1.status(), ForceStatus::Cancelled);
+        assert_eq!(force2.status(), ForceStatus::Pending);
+        assert_eq!(session.pending_forces().len(), 1);
+        let sent = game.take_sent();
+        assert_eq!(sent.len(), 6);
+        assert!(sent[0].contains(r#""command":"startup""#));
+        assert!(sent[1].contains(r#""command":"actions/register""#));
+        assert!(sent[2].contains(r#""message":"z","silent":false"#));
+        assert!(sent[3].contains(r#""message":"a\nb","silent":true"#));
+        assert!(sent[4].contains(r#""message":"c","silent":false"#));
+        assert!(sent[5].contains(r#""query":"2""#));
+    }
+
+    #[test]
+    fn test_resume() {
+        let game = TestGame::with_session();
+        game.initialize().unwrap();
+        game.unregister_actions::<Move>().unwrap();
+        let force = game.force_actions::<Shoot>("a".into()).send().unwrap();
+        game.take_sent();
+        game.resume().unwrap();
+        assert_eq!(
+            game.take_sent(),
+            [
+                r#"{"command":"startup","game":"Test"}"#,
+                r#"{"command":"actions/register","data":{"actions":[{"name":"shoot","description":"test2","schema":{}}]},"game":"Test"}"#,
+            ]
+        );
+        assert_eq!(force.status(), super::ForceStatus::Cancelled);
+        let session = game.session().unwrap();
+        assert_eq!(session.registered_names(), ["shoot"]);
+        // without registered actions, there's nothing to restore
+        game.unregister_all_actions().unwrap();
+        game.take_sent();
+        game.resume().unwrap();
+        assert_eq!(game.take_sent(), [r#"{"command":"startup","game":"Test"}"#]);
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        let game = TestGame::default();
+        game.handle_message(tungstenite::Message::text(
+            r#"{"command":"some/new_command","data":{"x":1}}"#,
+        ))
+        .unwrap();
+        assert!(game.take_sent().is_empty());
+        assert!(game
+            .handle_message(tungstenite::Message::text(r#"{"command":"action"}"#))
+            .is_err());
+        assert!(game.take_sent().is_empty());
+        assert!(game
+            .handle_message(tungstenite::Message::text(
+                r#"{"command":"action","data":{"id":"1"}}"#
+            ))
+            .is_err());
+        assert_eq!(
+            game.take_sent(),
+            [
+                r#"{"command":"action/result","data":{"id":"1","success":false,"message":"The action is malformed: missing field `name`."},"game":"Test"}"#
+            ]
+        );
+        assert!(game
+            .handle_message(tungstenite::Message::text(r#"{"data":{"x":1}}"#))
+            .is_err());
+    }
+
+    #[test]
+    fn test_action_names() {
+        let game = TestGame::default();
+        let action = |name: &'static str| crate::schema::Action {
+            name: name.into(),
+            description: "test".into(),
+            schema: Default::default(),
+        };
+        for name in ["Move", "move now", ""] {
+            assert!(matches!(
+                game.register_actions_raw(vec![action(name)]),
+                Err(super::Error::InvalidActionName(_))
+            ));
+        }
+        let err = game
+            .register_actions_raw(vec![action("a"), action("b"), action("a"), action("a")])
+            .unwrap_err();
+        assert!(matches!(err, super::Error::DuplicateActionNames(ref names) if names == &["a"]));
+        assert!(game.take_sent().is_empty());
+        game.register_actions_raw(vec![action("use_item-2")])
+            .unwrap();
+        assert_eq!(game.take_sent().len(), 1);
+    }
+
+    #[test]
+    fn test_deferred_result() {
+        let game = TestGame {
+            defer: true,
+            ..TestGame::with_session()
+        };
+        game.initialize().unwrap();
+        game.take_sent();
+        game.handle_message(tungstenite::Message::text(
+            r#"{"command":"action","data":{"id":"1","name":"shoot"}}"#,
+        ))
+        .unwrap();
+        assert!(game.take_sent().is_empty());
+        let pending = game.deferred.borrow_mut().pop().unwrap();
+        assert_eq!(pending.id(), "1");
+        assert_eq!(game.session().unwrap().pending_actions(), ["1"]);
+        assert!(matches!(
+            game.send_action_result("2", true, None),
+            Err(super::Error::UnexpectedActionResult(_))
+        ));
+        game.send_action_result(pending.id(), true, Some("shot".into()))
+            .unwrap();
+        assert_eq!(
+            game.take_sent(),
+            [
+                r#"{"command":"action/result","data":{"id":"1","success":true,"message":"shot"},"game":"Test"}"#
+            ]
+        );
+        assert!(game.send_action_result(pending.id(), true, None).is_err());
+        assert!(game.session().unwrap().pending_actions().is_empty());
+        assert!(game.defer_action_result().is_none());
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn test_validation() {
+        let game = TestGame {
+            session: Some(Session::new().with_validation(true)),
+            ..TestGame::default()
+        };
+        game.initialize().unwrap();
+        game.take_sent();
+        game.handle_message(tungstenite::Message::text(
+            r#"{"command":"action","data":{"id":"1","name":"move","data":"{\"x\":-1,\"y\":2}"}}"#,
+        ))
+        .unwrap();
+        let sent = game.take_sent();
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].contains(r#""success":false"#));
+        assert!(sent[0].contains(
+            "The action data doesn't match the schema: `x`: -1 is less than the minimum of 0"
+        ));
+        game.handle_message(tungstenite::Message::text(
+            r#"{"command":"action","data":{"id":"2","name":"move","data":"{\"x\":1,\"y\":2}"}}"#,
+        ))
+        .unwrap();
+        assert_eq!(
+            game.take_sent(),
+            [
+                r#"{"command":"action/result","data":{"id":"2","success":true,"message":"moved"},"game":"Test"}"#
+            ]
+        );
+
+        /// Aim action
+        #[allow(dead_code)]
+        #[derive(schemars::JsonSchema)]
+        struct Aim {
+            at: (u8, u8),
+        }
+        game.register_actions_raw(vec![crate::schema::Action {
+            name: "aim".into(),
+            description: "Aim".into(),
+            schema: schemars::schema_for!(Aim),
+        }])
+        .unwrap();
+        game.take_sent();
+        game.handle_message(tungstenite::Message::text(
+            r#"{"command":"action","data":{"id":"3","name":"aim","data":"{\"at\":[1,\"a\"]}"}}"#,
+        ))
+        .unwrap();
+        let sent = game.take_sent();
+        assert!(sent[0].contains(r#""success":false"#));
+        assert!(sent[0].contains("The action data doesn't match the schema: `at[1]`"));
+    }
+
+    #[test]
+    fn test_action_errors() {
+        let game = TestGame::with_session();
+        game.initialize().unwrap();
+        game.take_sent();
+        let result = |data: &str| {
+            game.handle_message(tungstenite::Message::text(format!(
+                r#"{{"command":"action","data":{{"id":"1",{data}}}}}"#
+            )))
+            .unwrap();
+            let sent = game.take_sent();
+            let sent: serde_json::Value = serde_json::from_str(&sent[0]).unwrap();
+            assert_eq!(sent["data"]["success"], false);
+            sent["data"]["message"].as_str().unwrap().to_owned()
+        };
+        assert_eq!(
+            result(r#""name":"jump""#),
+            "There is no action named `jump`. The available actions are: `move`, `shoot`."
+        );
+        assert_eq!(
+            result(r#""name":"move","data":"{\"x\":\"a\",\"y\":1}""#),
+            "Invalid value for `x`: error parsing number, expected an integer of at least 0."
+        );
+        assert_eq!(
+            result(r#""name":"move","data":"{\"x\":1}""#),
+            "Invalid value for the action data: missing field `y`."
+        );
+        assert_eq!(
+            result(r#""name":"move","data":"{x:""#),
+            "The action data is not valid JSON (line 1, column 4): expected array, boolean, null, \
+             number, object, or string."
+        );
+    }
+
+    #[test]
+    fn test_coercion() {
+        let game = TestGame {
+            session: Some(Session::new().with_coercion(true)),
+            ..TestGame::default()
+        };
+        game.initialize().unwrap();
+        game.take_sent();
+        game.handle_message(tungstenite::Message::text(
+            r#"{"command":"action","data":{"id":"1","name":"move","data":"{\"x\":\"1\",\"y\":2.0}"}}"#,
+        ))
+        .unwrap();
+        assert_eq!(
+            game.take_sent(),
+            [
+                r#"{"command":"action/result","data":{"id":"1","success":true,"message":"moved"},"game":"Test"}"#
+            ]
+        );
+        game.handle_message(tungstenite::Message::text(
+            r#"{"command":"action","data":{"id":"2","name":"move","data":"{\"x\":\"a\",\"y\":2}"}}"#,
+        ))
+        .unwrap();
+        let sent = game.take_sent();
+        assert!(sent[0].contains(r#""success":false"#));
+
+        // coerced data can't be borrowed from
+        /// Say something
+        #[derive(Debug, schemars::JsonSchema, Deserialize)]
+        struct Say<'a> {
+            text: &'a str,
+            loud: bool,
+        }
+
+        #[derive(Debug, Deserialize)]
+        enum SayAction<'a> {
+            #[serde(borrow, rename = "say")]
+            Say(Say<'a>),
+        }
+
+        impl crate::game::Action for Say<'_> {
+            fn name() -> &'static str {
+                "say"
+            }
+            fn description() -> &'static str {
+                "Say something"
+            }
+        }
+
+        let session = Session::new().with_coercion(true);
+        let mut actions = <Say as ActionMetadata>::actions();
+        cleanup_action(&mut actions[0]);
+        session.register(actions);
+        let parse = |data| {
+            super::parse_action::<SayAction>(
+                "say",
+                Some(data),
+                Some(&session),
+                &[ParseStage::Strict],
+            )
+        };
+        let SayAction::Say(say) = parse(r#"{"text":"hi","loud":true}"#).unwrap().action;
+        assert_eq!((say.text, say.loud), ("hi", true));
+        assert!(matches!(
+            parse(r#"{"text":"hi","loud":"true"}"#),
+            Err(super::ActionError::InvalidData { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_stages() {
+        let mut game = TestGame::with_session();
+        let result = |game: &TestGame, data: &str| {
+            game.handle_message(tungstenite::Message::text(format!(
+                r#"{{"command":"action","data":{{"id":"1","name":"move","data":{}}}}}"#,
+                serde_json::to_string(data).unwrap()
+            )))
+            .unwrap();
+            let sent = game.take_sent();
+            let sent: serde_json::Value = serde_json::from_str(sent.last().unwrap()).unwrap();
+            sent["data"]["message"].as_str().unwrap().to_owned()
+        };
+        let data = "Sure!\n```json\n{\"x\": 1, \"y\": 2,\n```";
+        game.initialize().unwrap();
+        assert!(result(&game, data).starts_with("The action data is not valid JSON"));
+        game.parse_stages = vec![ParseStage::Strict, ParseStage::Json5, ParseStage::Repair];
+        assert_eq!(result(&game, data), "moved");
+        assert_eq!(
+            result(&game, r#"{"x": "a", "y": 2}"#),
+            "Invalid value for `x`: invalid type: string \"a\", expected an integer of at least \
+             0."
+        );
+    }
+
+    #[test]
+    fn test_report_execution() {
+        let game = TestGame::with_session();
+        game.initialize().unwrap();
+        for (id, name) in [("1", "move"), ("2", "shoot")] {
+            game.handle_message(tungstenite::Message::text(format!(
+                r#"{{"command":"action","data":{{"id":"{id}","name":"{name}","data":"{{\"x\":1,\"y\":2}}"}}}}"#
+            )))
+            .unwrap();
+        }
+        game.take_sent();
+        game.report_execution("1", false, Some("A wall is in the way.".into()))
+            .unwrap();
+        assert_eq!(
+            game.take_sent(),
+            [
+                r#"{"command":"context","data":{"message":"Your `move` action failed when it was carried out. A wall is in the way.","silent":false},"game":"Test"}"#
+            ]
+        );
+        // already reported
+        assert!(matches!(
+            game.report_execution("1", true, None),
+            Err(super::Error::UnexpectedExecutionReport(id)) if id == "1"
+        ));
+        // answered with a failure
+        assert!(game.report_execution("2", true, None).is_err());
+        assert!(game.take_sent().is_empty());
+        // answered before the session was reset
+        game.handle_message(tungstenite::Message::text(
+            r#"{"command":"action","data":{"id":"3","name":"move","data":"{\"x\":1,\"y\":2}"}}"#,
+        ))
+        .unwrap();
+        let session = game.session().unwrap();
+        session.begin_action("4", "move");
+        game.initialize().unwrap();
+        assert!(matches!(
+            game.report_execution("3", true, None),
+            Err(super::Error::UnexpectedExecutionReport(id)) if id == "3"
+        ));
+        assert_eq!(session.current_id(), None);
+    }
+
+    #[test]
+    fn test_watchdog() {
+        use std::{
+            sync::{Arc, Mutex},
+            time::{Duration, Instant},
+        };
+
+        use super::Watchdog;
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let watchdog = Watchdog::new(Duration::from_secs(1)).with_callback({
+            let reported = reported.clone();
+            move |id, name, _| reported.lock().unwrap().push(format!("{id}:{name}"))
+        });
+        let session = Session::new().with_watchdog(watchdog.clone());
+        let start = Instant::now();
+        session.begin_action("1", "shoot");
+        assert!(!session.end_action(start));
+        session.begin_action("2", "shoot");
+        assert!(!session.end_action(start + Duration::from_secs(2)));
+        assert_eq!(*reported.lock().unwrap(), ["2:shoot"]);
+
+        let game = TestGame {
+            session: Some(
+                Session::new().with_watchdog(watchdog.with_deadline(Duration::from_secs(3))),
+            ),
+            defer: true,
+            ..TestGame::default()
+        };
+        game.initialize().unwrap();
+        let start = Instant::now();
+        game.handle_message(tungstenite::Message::text(
+            r#"{"command":"action","data":{"id":"3","name":"shoot"}}"#,
+        ))
+        .unwrap();
+        let session = game.session().unwrap();
+        assert!(session.overdue(start).is_empty());
+        assert!(session.overdue(start + Duration::from_secs(2)).is_empty());
+        assert_eq!(*reported.lock().unwrap(), ["2:shoot", "3:shoot"]);
+        // reported only once
+        assert!(session.overdue(start + Duration::from_secs(2)).is_empty());
+        assert_eq!(reported.lock().unwrap().len(), 2);
+        assert_eq!(
+            session.overdue(Instant::now() + Duration::from_secs(4)),
+            [("3".to_owned(), "shoot".to_owned())]
+        );
+        assert!(game.send_action_result("3", true, None).is_err());
+    }
+
+    struct AsyncTestGame(TestGame);
+
+    impl AsyncGame for AsyncTestGame {
+        const NAME: &'static str = "Test";
+        type Actions<'a> = Action;
+        async fn handle_action<'a>(
+            &self,
+            action: Self::Actions<'a>,
+        ) -> Result<
+            Option<impl 'static + Into<Cow<'static, str>>>,
+            Option<impl 'static + Into<Cow<'static, str>>>,
+        > {
+            std::future::ready(()).await;
+            Game::handle_action(&self.0, action)
+        }
+        fn reregister_actions(&self) {
+            self.register_actions::<Action>().unwrap();
+        }
+        fn send_command(&self, message: tungstenite::Message) {
+            self.0.send_command(message);
+        }
+    }
+
+    fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+        let mut fut = std::pin::pin!(fut);
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        loop {
+            if let std::task::Poll::Ready(ret) = fut.as_mut().poll(&mut cx) {
+                return ret;
+            }
+        }
+    }
+
+    #[test]
+    fn test_async() {
+        let game = std::rc::Rc::new(AsyncTestGame(TestGame::default()));
+        game.initialize().unwrap();
+        assert_eq!(game.0.take_sent().len(), 2);
+        let fut = game.handle_message(tungstenite::Message::text(
+            r#"{"command":"action","data":{"id":"1","name":"move","data":"{\"x\":1,\"y\":2}"}}"#,
+        ));
+        assert!(game.0.take_sent().is_empty());
+        block_on(fut).unwrap();
+        assert_eq!(
+            game.0.take_sent(),
+            [
+                r#"{"command":"action/result","data":{"id":"1","success":true,"message":"moved"},"game":"Test"}"#
+            ]
+        );
+    }
+
+    #[allow(dead_code)]
+    #[derive(schemars::JsonSchema)]
+    struct Tree {
+        children: Vec<Tree>,
+    }
+
+    /// Nested action
+    #[allow(dead_code)]
+    #[derive(schemars::JsonSchema)]
+    struct Nested {
+        /// The target
+        target: Move,
+        other: Option<Move>,
+        tree: Tree,
+    }
+
+    impl crate::game::Action for Nested {
+        fn name() -> &'static str {
+            "nested"
+        }
+        fn description() -> &'static str {
+            "Nested action"
+        }
+    }
+
+    #[test]
+    fn test_schema_settings() {
+        use schemars::gen::SchemaSettings;
+        let mv = serde_json::json!({
+            "type": "object",
+            "required": ["x", "y"],
+            "properties": {
+                "x": { "type": "integer", "format": "uint32", "minimum": 0.0 },
+                "y": { "type": "integer", "format": "uint32", "minimum": 0.0 },
+            },
+        });
+        let tree = serde_json::json!({
+            "type": "object",
+            "required": ["children"],
+            "properties": {
+                "children": { "type": "array", "items": { "$ref": "#/definitions/Tree" } },
+            },
+        });
+        for (settings, other) in [
+            (
+                SchemaSettings::draft07(),
+                serde_json::json!({ "anyOf": [mv, { "type": "null" }] }),
+            ),
+            (
+                SchemaSettings::openapi3(),
+                serde_json::json!({ "allOf": [mv], "nullable": true }),
+            ),
+        ] {
+            let mut actions = <Nested as ActionMetadata>::actions_with(&settings);
+            cleanup_action(&mut actions[0]);
+            assert_eq!(
+                serde_json::to_value(&actions[0].schema).unwrap(),
+                serde_json::json!({
+                    "type": "object",
+                    "required": ["target", "tree"],
+                    "properties": {
+                        "target": mv,
+                        "other": other,
+                        "tree": tree,
+                    },
+                    "definitions": { "Tree": tree },
+                })
+            );
+        }
+    }
+
     #[test]
     fn test() {
         use super::Actions;
@@ -682,13 +2505,11 @@ mod test {
               {
                 "name": "shoot",
                 "description": "test 2",
-                "schema": {
-                  "type": "null"
-                }
+                "schema": {}
               }
             ]"#
             .to_string()
-            .replace(|x| x == ' ' || x == '\n', "")
+            .replace([' ', '\n'], "")
         );
         #[cfg(not(feature = "strip-trailing-zeroes"))]
         assert_eq!(
@@ -709,13 +2530,11 @@ mod test {
               {
                 "name": "shoot",
                 "description": "test 2",
-                "schema": {
-                  "type": "null"
-                }
+                "schema": {}
               }
             ]"#
             .to_string()
-            .replace(|x| x == ' ' || x == '\n', "")
+            .replace([' ', '\n'], "")
         );
     }
 }
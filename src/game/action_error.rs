@@ -0,0 +1,344 @@
+//! Describing invalid actions in a way Neuro can act on
+use std::{borrow::Cow, fmt};
+
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+use serde_path_to_error::{Path, Segment};
+
+/// Why an incoming action was rejected before reaching
+/// [`Game::handle_action`](super::Game::handle_action).
+///
+/// The failure result sent to Neuro is rendered via
+/// [`Game::action_error_message`](super::Game::action_error_message), which uses the
+/// [`Display`](fmt::Display) impl (short plain English) by default.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ActionError {
+    /// The action isn't registered. This requires a [`Session`](super::Session), since otherwise
+    /// it isn't known what's registered.
+    UnknownAction {
+        /// The name of the action.
+        name: String,
+        /// The names of the currently registered actions.
+        valid_actions: Vec<Cow<'static, str>>,
+    },
+    /// The action data isn't valid JSON.
+    Syntax {
+        /// The parser's error message.
+        message: String,
+        /// The one-based line and column of the error, if known.
+        location: Option<(usize, usize)>,
+    },
+    /// The action data doesn't match the action's type.
+    InvalidData {
+        /// The path of the offending field (e.g. `items[0].name`), or an empty string if the
+        /// problem is with the data as a whole.
+        path: String,
+        /// A description of what was expected at that path (e.g. `"an integer"`), taken from the
+        /// registered schema. This requires a [`Session`](super::Session).
+        expected: Option<String>,
+        /// The deserializer's error message.
+        message: String,
+    },
+    /// The action data doesn't match the registered schema (see
+    /// [`Session::with_validation`](super::Session::with_validation)).
+    SchemaMismatch {
+        /// The paths of the offending fields (in the same format as
+        /// [`ActionError::InvalidData::path`]) along with the problems found there.
+        errors: Vec<(String, String)>,
+    },
+}
+
+/// Format a path to a field, e.g. ``"`a.b`"``, or `"the action data"` for an empty path.
+fn field(path: &str) -> Cow<'_, str> {
+    if path.is_empty() {
+        "the action data".into()
+    } else {
+        format!("`{path}`").into()
+    }
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownAction {
+                name,
+                valid_actions,
+            } if valid_actions.is_empty() => write!(
+                f,
+                "There is no action named `{name}`, and no actions are currently available."
+            ),
+            Self::UnknownAction {
+                name,
+                valid_actions,
+            } => write!(
+                f,
+                "There is no action named `{name}`. The available actions are: `{}`.",
+                valid_actions.join("`, `")
+            ),
+            Self::Syntax {
+                message,
+                location: Some((line, column)),
+            } => write!(
+                f,
+                "The action data is not valid JSON (line {line}, column {column}): {message}."
+            ),
+            Self::Syntax { message, .. } => {
+                write!(f, "The action data is not valid JSON: {message}.")
+            }
+            Self::InvalidData {
+                path,
+                expected: Some(expected),
+                message,
+            } if !message.starts_with("missing field") && !message.starts_with("unknown field") => {
+                // serde's "expected" part describes the Rust type, which isn't useful to Neuro
+                let message = match message.strip_prefix("invalid ") {
+                    Some(_) => message.split(", expected ").next().unwrap_or(message),
+                    None => message,
+                };
+                write!(
+                    f,
+                    "Invalid value for {}: {message}, expected {expected}.",
+                    field(path)
+                )
+            }
+            Self::InvalidData { path, message, .. } => {
+                write!(f, "Invalid value for {}: {message}.", field(path))
+            }
+            Self::SchemaMismatch { errors } => {
+                f.write_str("The action data doesn't match the schema:")?;
+                for (i, (path, message)) in errors.iter().enumerate() {
+                    let sep = if i == 0 { " " } else { "; " };
+                    write!(f, "{sep}{}: {message}", field(path))?;
+                }
+                f.write_str(".")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ActionError {}
+
+impl ActionError {
+    pub(crate) fn syntax(err: json5::Error) -> Self {
+        let json5::Error::Message { msg, location } = err;
+        // the parser's message is a multi-line snippet ending with `= <the actual message>`
+        let message = match msg.rsplit_once("\n  = ") {
+            Some((_, message)) => message.trim_end_matches('.').to_owned(),
+            None => msg,
+        };
+        Self::Syntax {
+            message,
+            location: location.map(|x| (x.line, x.column)),
+        }
+    }
+
+    pub(crate) fn strict_syntax(err: serde_json::Error) -> Self {
+        Self::Syntax {
+            message: strip_location(&err),
+            location: (err.line() != 0).then(|| (err.line(), err.column())),
+        }
+    }
+}
+
+/// Get a `serde_json` error's message without the location it appends.
+pub(crate) fn strip_location(err: &serde_json::Error) -> String {
+    let message = err.to_string();
+    let suffix = format!(" at line {} column {}", err.line(), err.column());
+    match message.strip_suffix(&suffix) {
+        Some(message) => message.to_owned(),
+        None => message,
+    }
+}
+
+/// Format a serde path (`a.b[0]`, or an empty string for the root).
+pub(crate) fn format_path(path: &Path) -> String {
+    let mut ret = String::new();
+    for segment in path {
+        match segment {
+            Segment::Seq { index } => ret += &format!("[{index}]"),
+            Segment::Map { key } | Segment::Enum { variant: key } => {
+                if !ret.is_empty() {
+                    ret.push('.');
+                }
+                ret += key;
+            }
+            Segment::Unknown => {
+                if !ret.is_empty() {
+                    ret.push('.');
+                }
+                ret.push('?');
+            }
+        }
+    }
+    ret
+}
+
+/// Convert a JSON pointer into the same format as [`format_path`].
+#[cfg(feature = "validation")]
+pub(crate) fn pointer_to_path(pointer: &str) -> String {
+    let mut ret = String::new();
+    for segment in pointer.split('/').skip(1) {
+        let segment = segment.replace("~1", "/").replace("~0", "~");
+        if segment.parse::<usize>().is_ok() {
+            ret += &format!("[{segment}]");
+        } else {
+            if !ret.is_empty() {
+                ret.push('.');
+            }
+            ret += &segment;
+        }
+    }
+    ret
+}
+
+/// Describe what the schema expects at the given path, e.g. `"an integer of at least 0"`.
+pub(crate) fn expected_at(root: &RootSchema, path: &Path) -> Option<String> {
+    let mut schema = resolve(root, &root.schema)?;
+    for segment in path {
+        schema = match segment {
+            Segment::Seq { index } => match schema.array.as_ref()?.items.as_ref()? {
+                SingleOrVec::Single(x) => object(root, x)?,
+                SingleOrVec::Vec(x) => object(root, x.get(*index)?)?,
+            },
+            Segment::Map { key } => {
+                let obj = schema.object.as_ref()?;
+                object(
+                    root,
+                    obj.properties
+                        .get(key)
+                        .or(obj.additional_properties.as_deref())?,
+                )?
+            }
+            Segment::Enum { .. } => schema,
+            Segment::Unknown => return None,
+        };
+    }
+    describe(root, schema)
+}
+
+pub(crate) fn object<'a>(root: &'a RootSchema, schema: &'a Schema) -> Option<&'a SchemaObject> {
+    match schema {
+        Schema::Object(x) => resolve(root, x),
+        Schema::Bool(_) => None,
+    }
+}
+
+/// Follow references, and look through `allOf` with a single schema and `anyOf` with a `null`
+/// alternative (both are generated by schemars).
+pub(crate) fn resolve<'a>(
+    root: &'a RootSchema,
+    mut schema: &'a SchemaObject,
+) -> Option<&'a SchemaObject> {
+    // the limit protects against reference cycles
+    for _ in 0..16 {
+        if let Some(reference) = &schema.reference {
+            let (_, name) = reference.rsplit_once('/')?;
+            schema = match root.definitions.get(name)? {
+                Schema::Object(x) => x,
+                Schema::Bool(_) => return None,
+            };
+            continue;
+        }
+        let Some(sub) = schema.subschemas.as_deref() else {
+            return Some(schema);
+        };
+        let options = match (&sub.all_of, &sub.any_of) {
+            (Some(x), None) if x.len() == 1 => x,
+            (None, Some(x)) => x,
+            _ => return Some(schema),
+        };
+        let mut options = options
+            .iter()
+            .filter(|x| !matches!(x, Schema::Object(x) if is_null(x)));
+        match (options.next(), options.next()) {
+            (Some(Schema::Object(x)), None) if schema.instance_type.is_none() => schema = x,
+            _ => return Some(schema),
+        }
+    }
+    None
+}
+
+fn is_null(schema: &SchemaObject) -> bool {
+    matches!(&schema.instance_type, Some(SingleOrVec::Single(x)) if **x == InstanceType::Null)
+}
+
+fn describe(root: &RootSchema, schema: &SchemaObject) -> Option<String> {
+    if let Some(values) = &schema.enum_values {
+        let values = values.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        return Some(match &values[..] {
+            [value] => value.clone(),
+            values => format!("one of {}", values.join(", ")),
+        });
+    }
+    if let Some(value) = &schema.const_value {
+        return Some(value.to_string());
+    }
+    if let Some(options) = schema.subschemas.as_ref().and_then(|x| x.any_of.as_ref()) {
+        let options = options
+            .iter()
+            .map(|x| object(root, x).and_then(|x| describe(root, x)))
+            .collect::<Option<Vec<_>>>()?;
+        return Some(options.join(" or "));
+    }
+    let types = match schema.instance_type.as_ref()? {
+        SingleOrVec::Single(x) => vec![**x],
+        SingleOrVec::Vec(x) => x.clone(),
+    };
+    let mut ret = types
+        .iter()
+        .map(|x| match x {
+            InstanceType::Null => "null",
+            InstanceType::Boolean => "a boolean",
+            InstanceType::Object => "an object",
+            InstanceType::Array => "an array",
+            InstanceType::Number => "a number",
+            InstanceType::String => "a string",
+            InstanceType::Integer => "an integer",
+        })
+        .collect::<Vec<_>>()
+        .join(" or ");
+    if let Some(num) = &schema.number {
+        match (num.minimum, num.maximum) {
+            (Some(min), Some(max)) => ret += &format!(" between {min} and {max}"),
+            (Some(min), None) => ret += &format!(" of at least {min}"),
+            (None, Some(max)) => ret += &format!(" of at most {max}"),
+            (None, None) => {}
+        }
+    }
+    Some(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::ActionError;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            ActionError::UnknownAction {
+                name: "jump".to_owned(),
+                valid_actions: vec!["move".into(), "shoot".into()],
+            }
+            .to_string(),
+            "There is no action named `jump`. The available actions are: `move`, `shoot`."
+        );
+        assert_eq!(
+            ActionError::InvalidData {
+                path: "x".to_owned(),
+                expected: Some("an integer of at least 0".to_owned()),
+                message: "invalid type: string \"a\", expected u32".to_owned(),
+            }
+            .to_string(),
+            "Invalid value for `x`: invalid type: string \"a\", expected an integer of at least 0."
+        );
+        assert_eq!(
+            ActionError::InvalidData {
+                path: String::new(),
+                expected: None,
+                message: "missing field `y`".to_owned(),
+            }
+            .to_string(),
+            "Invalid value for the action data: missing field `y`."
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! Fixing up sloppy action data
+use std::fmt;
+
+use schemars::schema::{InstanceType, RootSchema, SchemaObject, SingleOrVec};
+use serde_json::Value;
+
+use super::action_error::{object, resolve};
+
+/// A change made to incoming action data to make it match the registered schema (see
+/// [`Session::with_coercion`](super::Session::with_coercion)).
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Coercion {
+    /// The path of the changed field (e.g. `items[0].count`), or an empty string for the data as
+    /// a whole.
+    pub path: String,
+    /// The value that was sent by Neuro.
+    pub from: Value,
+    /// The value it was replaced with.
+    pub to: Value,
+}
+
+impl fmt::Display for Coercion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{} -> {}", self.from, self.to)
+        } else {
+            write!(f, "`{}`: {} -> {}", self.path, self.from, self.to)
+        }
+    }
+}
+
+/// Coerce the value to match the schema where that can be done safely, returning the coercions
+/// that were applied.
+pub(crate) fn coerce(root: &RootSchema, value: &mut Value) -> Vec<Coercion> {
+    let mut ret = Vec::new();
+    if let Some(schema) = resolve(root, &root.schema) {
+        visit(root, schema, value, &mut String::new(), &mut ret);
+    }
+    ret
+}
+
+fn visit(
+    root: &RootSchema,
+    schema: &SchemaObject,
+    value: &mut Value,
+    path: &mut String,
+    ret: &mut Vec<Coercion>,
+) {
+    if let Some(to) = coerce_scalar(root, schema, value) {
+        ret.push(Coercion {
+            path: path.clone(),
+            from: std::mem::replace(value, to.clone()),
+            to,
+        });
+    }
+    match value {
+        Value::Object(map) => {
+            let Some(obj) = schema.object.as_ref() else {
+                return;
+            };
+            for (key, value) in map {
+                let Some(schema) = obj
+                    .properties
+                    .get(key)
+                    .or(obj.additional_properties.as_deref())
+                    .and_then(|x| object(root, x))
+                else {
+                    continue;
+                };
+                let len = path.len();
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+                visit(root, schema, value, path, ret);
+                path.truncate(len);
+            }
+        }
+        Value::Array(arr) => {
+            let Some(items) = schema.array.as_ref().and_then(|x| x.items.as_ref()) else {
+                return;
+            };
+            for (i, value) in arr.iter_mut().enumerate() {
+                let schema = match items {
+                    SingleOrVec::Single(x) => object(root, x),
+                    SingleOrVec::Vec(x) => x.get(i).and_then(|x| object(root, x)),
+                };
+                let Some(schema) = schema else {
+                    continue;
+                };
+                let len = path.len();
+                path.push_str(&format!("[{i}]"));
+                visit(root, schema, value, path, ret);
+                path.truncate(len);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn matches_type(ty: InstanceType, value: &Value) -> bool {
+    match ty {
+        InstanceType::Null => value.is_null(),
+        InstanceType::Boolean => value.is_boolean(),
+        InstanceType::Object => value.is_object(),
+        InstanceType::Array => value.is_array(),
+        InstanceType::Number => value.is_number(),
+        InstanceType::String => value.is_string(),
+        InstanceType::Integer => value.is_i64() || value.is_u64(),
+    }
+}
+
+/// The string values allowed by the schema's `enum` (or by the `enum`s of its `anyOf`/`oneOf`
+/// alternatives, which is what schemars generates for enums with data).
+fn enum_strings<'a>(root: &'a RootSchema, schema: &'a SchemaObject) -> Vec<&'a str> {
+    let mut ret = Vec::new();
+    let sub = schema.subschemas.as_deref();
+    let options = sub
+        .and_then(|x| x.any_of.as_ref())
+        .into_iter()
+        .chain(sub.and_then(|x| x.one_of.as_ref()))
+        .flatten()
+        .filter_map(|x| object(root, x));
+    for schema in std::iter::once(schema).chain(options) {
+        ret.extend(
+            schema
+                .enum_values
+                .iter()
+                .flatten()
+                .filter_map(Value::as_str),
+        );
+    }
+    ret
+}
+
+/// Coerce a scalar to one of the schema's types (or to one of its enum values).
+fn coerce_scalar(root: &RootSchema, schema: &SchemaObject, value: &Value) -> Option<Value> {
+    if let Value::String(s) = value {
+        let values = enum_strings(root, schema);
+        if !values.is_empty() && !values.contains(&s.as_str()) {
+            // only coerce if it's unambiguous
+            let mut matching = values.iter().filter(|x| x.eq_ignore_ascii_case(s.trim()));
+            if let (Some(x), None) = (matching.next(), matching.next()) {
+                return Some(Value::String((*x).to_owned()));
+            }
+        }
+    }
+    let types = match schema.instance_type.as_ref()? {
+        SingleOrVec::Single(x) => vec![**x],
+        SingleOrVec::Vec(x) => x.clone(),
+    };
+    if types.iter().any(|x| matches_type(*x, value)) {
+        return None;
+    }
+    types.into_iter().find_map(|ty| match (ty, value) {
+        (InstanceType::Integer, Value::String(s)) => {
+            let s = s.trim();
+            if let Ok(x) = s.parse::<i64>() {
+                Some(x.into())
+            } else if let Ok(x) = s.parse::<u64>() {
+                Some(x.into())
+            } else {
+                integral(s.parse().ok()?)
+            }
+        }
+        (InstanceType::Integer, Value::Number(x)) => integral(x.as_f64()?),
+        (InstanceType::Number, Value::String(s)) => {
+            let x = s.trim().parse::<f64>().ok()?;
+            serde_json::Number::from_f64(x).map(Value::Number)
+        }
+        (InstanceType::Boolean, Value::String(s)) => match s.trim() {
+            x if x.eq_ignore_ascii_case("true") => Some(true.into()),
+            x if x.eq_ignore_ascii_case("false") => Some(false.into()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Convert a float to an integer if it has no fractional part (e.g. `5.0`).
+fn integral(x: f64) -> Option<Value> {
+    if x.fract() != 0.0 || !x.is_finite() {
+        return None;
+    }
+    // `u64::MAX as f64` is rounded up to 2^64, which doesn't fit
+    if x >= 0.0 && x < u64::MAX as f64 {
+        Some((x as u64).into())
+    } else if x < 0.0 && x >= i64::MIN as f64 {
+        Some((x as i64).into())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use schemars::JsonSchema;
+    use serde_json::json;
+
+    use super::{coerce, integral};
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    enum Direction {
+        Left,
+        Right,
+    }
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    struct Data {
+        count: u8,
+        scale: f32,
+        flag: bool,
+        direction: Direction,
+        items: Vec<Option<u32>>,
+        name: String,
+    }
+
+    #[test]
+    fn test_coerce() {
+        let schema = schemars::schema_for!(Data);
+        let mut value = json!({
+            "count": "5",
+            "scale": "1.5",
+            "flag": "True",
+            "direction": "left",
+            "items": [1, 2.0, null, "x"],
+            "name": "5",
+        });
+        let coercions = coerce(&schema, &mut value);
+        assert_eq!(
+            value,
+            json!({
+                "count": 5,
+                "scale": 1.5,
+                "flag": true,
+                "direction": "Left",
+                "items": [1, 2, null, "x"],
+                "name": "5",
+            })
+        );
+        assert_eq!(
+            coercions
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                r#"`count`: "5" -> 5"#,
+                "`direction`: \"left\" -> \"Left\"",
+                r#"`flag`: "True" -> true"#,
+                "`items[1]`: 2.0 -> 2",
+                r#"`scale`: "1.5" -> 1.5"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_integral() {
+        assert_eq!(integral(5.0), Some(json!(5)));
+        assert_eq!(integral(-5.0), Some(json!(-5)));
+        assert_eq!(integral(0.5), None);
+        // 2^64 doesn't fit into a `u64`, and saturating it would change the value
+        assert_eq!(integral(u64::MAX as f64), None);
+        assert_eq!(integral(i64::MIN as f64), Some(json!(i64::MIN)));
+    }
+}
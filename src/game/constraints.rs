@@ -0,0 +1,402 @@
+//! Declarative constraints on action parameters
+//!
+//! This module is an implementation detail of `#[derive(neuro_sama::derive::Constraints)]`, only
+//! the [`Constraints`] trait is public API.
+use std::{borrow::Cow, collections::VecDeque, fmt, marker::PhantomData};
+
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+
+/// Constraints on the values of an action's fields that can't be expressed by its type (e.g. a
+/// `u8` coordinate that can only be 0, 1 or 2). It can be automatically implemented with
+/// `#[derive(neuro_sama::derive::Constraints)]` and the `#[neuro(...)]` field attributes:
+///
+/// - `#[neuro(range = 0..=2)]` - the value must be in the range (any kind of range works).
+/// - `#[neuro(len = 1..=20)]` - the string's length in characters (or the number of items in the
+///   collection) must be in the range. Empty ranges (e.g. `..0`) are rejected.
+/// - `#[neuro(one_of = ["rock", "paper", "scissors"])]` - the value must be one of the given
+///   values (which must implement `Serialize`).
+///
+/// For `Option` fields, the constraints only apply to `Some` values.
+///
+/// When the action type is part of an enum with `#[derive(neuro_sama::derive::Actions)]`, the
+/// constraints are added to the action's schema, and actions that violate them are rejected when
+/// they're deserialized.
+///
+/// ```
+/// # use neuro_sama::game::Constraints;
+/// #[derive(Debug, neuro_sama::derive::Constraints, schemars::JsonSchema, serde::Deserialize)]
+/// struct Move {
+///     #[neuro(range = 0..=2)]
+///     x: u8,
+///     #[neuro(range = 0..=2)]
+///     y: u8,
+/// }
+///
+/// assert!(Move { x: 1, y: 2 }.check().is_ok());
+/// assert_eq!(
+///     Move { x: 3, y: 2 }.check().unwrap_err(),
+///     "`x` must be between 0 and 2",
+/// );
+/// ```
+pub trait Constraints {
+    /// Add the constraints to the type's schema.
+    fn constrain_schema(schema: &mut RootSchema);
+
+    /// Check whether the value satisfies the constraints, returning a description of the
+    /// violated constraint if it doesn't.
+    fn check(&self) -> Result<(), String>;
+}
+
+/// Applies the [`Constraints`] of a type if it implements them (via an inherent method, which
+/// takes priority over the no-op [`NoConstraints`] fallback).
+#[doc(hidden)]
+pub struct ConstraintsOf<T>(pub PhantomData<T>);
+
+impl<T: Constraints> ConstraintsOf<T> {
+    pub fn constrain_schema(&self, schema: &mut RootSchema) {
+        T::constrain_schema(schema);
+    }
+
+    pub fn check(&self, value: &T) -> Result<(), String> {
+        value.check()
+    }
+}
+
+#[doc(hidden)]
+pub trait NoConstraints<T> {
+    fn constrain_schema(&self, schema: &mut RootSchema) {
+        let _ = schema;
+    }
+
+    fn check(&self, value: &T) -> Result<(), String> {
+        let _ = value;
+        Ok(())
+    }
+}
+
+impl<T> NoConstraints<T> for ConstraintsOf<T> {}
+
+/// The length of a value constrained with `#[neuro(len = ...)]`.
+#[doc(hidden)]
+pub trait Length {
+    /// What the length is measured in.
+    const UNIT: &'static str;
+
+    fn length(&self) -> usize;
+}
+
+impl Length for str {
+    const UNIT: &'static str = "characters";
+    fn length(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl Length for String {
+    const UNIT: &'static str = "characters";
+    fn length(&self) -> usize {
+        self.as_str().length()
+    }
+}
+
+impl Length for Cow<'_, str> {
+    const UNIT: &'static str = "characters";
+    fn length(&self) -> usize {
+        (**self).length()
+    }
+}
+
+impl<T> Length for [T] {
+    const UNIT: &'static str = "items";
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T, const N: usize> Length for [T; N] {
+    const UNIT: &'static str = "items";
+    fn length(&self) -> usize {
+        N
+    }
+}
+
+impl<T> Length for Vec<T> {
+    const UNIT: &'static str = "items";
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> Length for VecDeque<T> {
+    const UNIT: &'static str = "items";
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T: ?Sized + Length> Length for &T {
+    const UNIT: &'static str = T::UNIT;
+    fn length(&self) -> usize {
+        (**self).length()
+    }
+}
+
+#[doc(hidden)]
+pub fn length_unit<T: ?Sized + Length>(value: &T) -> &'static str {
+    let _ = value;
+    T::UNIT
+}
+
+/// The schema of a property of the root schema.
+///
+/// # Panics
+///
+/// If there's no such property, since the constraints would silently be missing from the schema
+/// otherwise. This happens if the derive got the field's name wrong, or if the field isn't a
+/// property of its own (e.g. with `#[serde(flatten)]` or `#[serde(skip)]`).
+#[doc(hidden)]
+pub fn property<'a>(schema: &'a mut RootSchema, name: &str) -> &'a mut SchemaObject {
+    let property = schema
+        .schema
+        .object
+        .as_mut()
+        .and_then(|x| x.properties.get_mut(name));
+    let Some(property) = property else {
+        panic!(
+            "the schema has no property `{name}` to add its `#[neuro(...)]` constraints to \
+             (check the field's `#[serde(...)]` attributes)"
+        );
+    };
+    if let Schema::Bool(true) = property {
+        *property = Schema::Object(SchemaObject::default());
+    }
+    match property {
+        Schema::Object(x) => x,
+        Schema::Bool(_) => panic!("the schema of property `{name}` can't be constrained"),
+    }
+}
+
+fn has_type(schema: &SchemaObject, ty: InstanceType) -> bool {
+    match &schema.instance_type {
+        Some(SingleOrVec::Single(x)) => **x == ty,
+        Some(SingleOrVec::Vec(x)) => x.contains(&ty),
+        None => false,
+    }
+}
+
+#[doc(hidden)]
+pub fn range(schema: &mut SchemaObject, min: Option<f64>, max: Option<f64>, inclusive: bool) {
+    // the bounds the range doesn't have are left alone (e.g. `minimum: 0` for unsigned integers)
+    let num = schema.number();
+    if min.is_some() {
+        num.minimum = min;
+    }
+    match max {
+        Some(_) if inclusive => num.maximum = max,
+        Some(_) => num.exclusive_maximum = max,
+        None => {}
+    }
+}
+
+#[doc(hidden)]
+pub fn len(schema: &mut SchemaObject, mut min: Option<usize>, max: Option<usize>, inclusive: bool) {
+    let max = match max {
+        Some(max) if !inclusive => Some(max.checked_sub(1).unwrap_or_else(|| {
+            // nothing is allowed (the derive rejects literal `..0`, but not constants), so make
+            // the schema unsatisfiable to agree with the check
+            min = Some(1);
+            0
+        })),
+        max => max,
+    };
+    let (min, max) = (min.map(|x| x as u32), max.map(|x| x as u32));
+    if has_type(schema, InstanceType::Array) {
+        let arr = schema.array();
+        arr.min_items = min;
+        arr.max_items = max;
+    } else {
+        let string = schema.string();
+        string.min_length = min;
+        string.max_length = max;
+    }
+}
+
+#[doc(hidden)]
+pub fn one_of(schema: &mut SchemaObject, values: Vec<serde_json::Value>) {
+    if has_type(schema, InstanceType::Null) {
+        schema.enum_values = Some(
+            values
+                .into_iter()
+                .chain([serde_json::Value::Null])
+                .collect(),
+        );
+    } else {
+        schema.enum_values = Some(values);
+    }
+}
+
+#[doc(hidden)]
+pub fn to_value<T: ?Sized + serde::Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+/// Describe a range, e.g. `"between 0 and 2"`.
+#[doc(hidden)]
+pub fn describe_range(
+    min: Option<&dyn fmt::Display>,
+    max: Option<&dyn fmt::Display>,
+    inclusive: bool,
+) -> String {
+    match (min, max, inclusive) {
+        (Some(min), Some(max), true) => format!("between {min} and {max}"),
+        (Some(min), Some(max), false) => format!("at least {min} and less than {max}"),
+        (Some(min), None, _) => format!("at least {min}"),
+        (None, Some(max), true) => format!("at most {max}"),
+        (None, Some(max), false) => format!("less than {max}"),
+        (None, None, _) => "anything".to_owned(),
+    }
+}
+
+/// Describe a list of allowed values, e.g. `"one of \"rock\", \"paper\""`.
+#[doc(hidden)]
+pub fn describe_values(values: &[serde_json::Value]) -> String {
+    let values = values.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    format!("one of {}", values.join(", "))
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+
+    use crate::{
+        self as neuro_sama,
+        game::{ActionMetadata, Actions, Constraints},
+    };
+
+    /// Play action
+    #[derive(Debug, crate::derive::Constraints, schemars::JsonSchema, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Play {
+        #[neuro(range = 0..=2)]
+        column: u8,
+        #[neuro(range = 0.0..1.0)]
+        strength: Option<f32>,
+        #[neuro(len = 1..=3, one_of = ["x", "o"])]
+        player_mark: String,
+        #[neuro(len = ..3)]
+        #[serde(rename = "tags")]
+        labels: Vec<String>,
+        #[neuro(range = ..=5)]
+        count: Option<u32>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, crate::derive::Actions)]
+    enum Action {
+        /// Play
+        #[name = "play"]
+        Play(Play),
+    }
+
+    #[test]
+    fn test_constraints() {
+        let schema = serde_json::to_value(&Action::actions()[0].schema).unwrap();
+        let props = &schema["properties"];
+        assert_eq!(props["column"]["minimum"], 0.0);
+        assert_eq!(props["column"]["maximum"], 2.0);
+        assert_eq!(props["strength"]["minimum"], 0.0);
+        assert_eq!(props["strength"]["exclusiveMaximum"], 1.0);
+        assert_eq!(props["playerMark"]["minLength"], 1);
+        assert_eq!(props["playerMark"]["maxLength"], 3);
+        assert_eq!(props["playerMark"]["enum"], serde_json::json!(["x", "o"]));
+        assert_eq!(props["tags"]["maxItems"], 2);
+        assert_eq!(props["count"]["minimum"], 0.0);
+        assert_eq!(props["count"]["maximum"], 5.0);
+
+        let parse = |data: &str| {
+            <Action as Actions>::deserialize(
+                "play",
+                &mut json5::Deserializer::from_str(data).unwrap(),
+            )
+            .map_err(|err| err.to_string())
+        };
+        assert!(parse(r#"{"column": 2, "playerMark": "x", "tags": []}"#).is_ok());
+        assert_eq!(
+            parse(r#"{"column": 3, "playerMark": "x", "tags": []}"#).unwrap_err(),
+            "`column` must be between 0 and 2"
+        );
+        assert_eq!(
+            parse(r#"{"column": 0, "strength": 1, "playerMark": "x", "tags": []}"#).unwrap_err(),
+            "`strength` must be at least 0 and less than 1"
+        );
+        assert_eq!(
+            parse(r#"{"column": 0, "playerMark": "", "tags": []}"#).unwrap_err(),
+            "`playerMark` must have between 1 and 3 characters"
+        );
+        assert_eq!(
+            parse(r#"{"column": 0, "playerMark": "y", "tags": []}"#).unwrap_err(),
+            r#"`playerMark` must be one of "x", "o""#
+        );
+        assert_eq!(
+            parse(r#"{"column": 0, "playerMark": "x", "tags": ["a", "b", "c"]}"#).unwrap_err(),
+            "`tags` must have less than 3 items"
+        );
+        assert!(Play {
+            column: 0,
+            strength: None,
+            player_mark: "o".to_owned(),
+            labels: Vec::new(),
+            count: Some(5),
+        }
+        .check()
+        .is_ok());
+    }
+
+    #[test]
+    fn test_empty_len() {
+        const MAX: usize = 0;
+
+        /// Empty action
+        #[derive(Debug, crate::derive::Constraints, schemars::JsonSchema, Deserialize)]
+        struct Empty {
+            #[neuro(len = ..MAX)]
+            x: String,
+        }
+
+        #[allow(dead_code)]
+        #[derive(Debug, crate::derive::Actions)]
+        enum Action {
+            /// Empty
+            #[name = "empty"]
+            Empty(Empty),
+        }
+
+        let schema = serde_json::to_value(&Action::actions()[0].schema).unwrap();
+        assert_eq!(schema["properties"]["x"]["minLength"], 1);
+        assert_eq!(schema["properties"]["x"]["maxLength"], 0);
+        assert!(Empty { x: String::new() }.check().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "the schema has no property `x`")]
+    fn test_missing_property() {
+        /// Skip action
+        #[allow(dead_code)]
+        #[derive(Debug, crate::derive::Constraints, schemars::JsonSchema, Deserialize)]
+        struct Skip {
+            #[neuro(range = 0..=2)]
+            #[serde(skip)]
+            x: u8,
+        }
+
+        #[allow(dead_code)]
+        #[derive(Debug, crate::derive::Actions)]
+        enum Action {
+            /// Skip
+            #[name = "skip"]
+            Skip(Skip),
+        }
+
+        Action::actions();
+    }
+}
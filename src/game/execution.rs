@@ -0,0 +1,132 @@
+//! Reporting what happened when an action was executed
+use std::borrow::Cow;
+
+/// The wording of the context messages sent by
+/// [`Api::report_execution`](super::Api::report_execution), and whether they're silent.
+///
+/// To use it, return it from [`Game::execution_template`](super::Game::execution_template).
+/// The templates can contain the placeholders `{name}` (the action's name), `{id}` (the action's
+/// id) and `{message}` (the message passed to
+/// [`Api::report_execution`](super::Api::report_execution), or nothing).
+#[derive(Clone, Debug)]
+pub struct ExecutionTemplate {
+    success: Cow<'static, str>,
+    failure: Cow<'static, str>,
+    silent: bool,
+}
+
+impl Default for ExecutionTemplate {
+    fn default() -> Self {
+        Self {
+            success: "Your `{name}` action was carried out. {message}".into(),
+            failure: "Your `{name}` action failed when it was carried out. {message}".into(),
+            silent: false,
+        }
+    }
+}
+
+impl ExecutionTemplate {
+    /// Create the default template.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the template for successful executions. The default is
+    /// ``"Your `{name}` action was carried out. {message}"``.
+    #[must_use]
+    pub fn with_success(mut self, template: impl Into<Cow<'static, str>>) -> Self {
+        self.success = template.into();
+        self
+    }
+
+    /// Set the template for failed executions. The default is
+    /// ``"Your `{name}` action failed when it was carried out. {message}"``.
+    #[must_use]
+    pub fn with_failure(mut self, template: impl Into<Cow<'static, str>>) -> Self {
+        self.failure = template.into();
+        self
+    }
+
+    /// Send the reports as silent context messages, so that Neuro isn't prompted to respond to
+    /// them. The default is `false`.
+    #[must_use]
+    pub fn with_silent(mut self, silent: bool) -> Self {
+        self.silent = silent;
+        self
+    }
+
+    /// Whether the reports are silent.
+    pub(crate) fn silent(&self) -> bool {
+        self.silent
+    }
+
+    /// Render the report for an action.
+    pub(crate) fn render(&self, id: &str, name: &str, success: bool, message: &str) -> String {
+        let template = if success {
+            &self.success
+        } else {
+            &self.failure
+        };
+        let mut ret = String::with_capacity(template.len() + message.len());
+        let mut rest = &**template;
+        while let Some(start) = rest.find('{') {
+            ret.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let placeholder = rest.find('}').and_then(|end| {
+                let value = match &rest[1..end] {
+                    "id" => id,
+                    "name" => name,
+                    "message" => message,
+                    _ => return None,
+                };
+                Some((value, end))
+            });
+            match placeholder {
+                Some((value, end)) => {
+                    ret.push_str(value);
+                    rest = &rest[end + 1..];
+                    // don't leave a double (or leading) space where an empty placeholder was
+                    if value.is_empty() && (ret.is_empty() || ret.ends_with(char::is_whitespace)) {
+                        rest = rest.trim_start();
+                    }
+                }
+                None => {
+                    ret.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        ret.push_str(rest);
+        ret.trim_end().to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ExecutionTemplate;
+
+    #[test]
+    fn test_render() {
+        let template = ExecutionTemplate::new();
+        assert_eq!(
+            template.render("1", "move", false, "A wall is in the way."),
+            "Your `move` action failed when it was carried out. A wall is in the way."
+        );
+        assert_eq!(
+            template.render("1", "move", true, ""),
+            "Your `move` action was carried out."
+        );
+        let template = template.with_success("{message} Your `{name}` action was carried out.");
+        assert_eq!(
+            template.render("1", "move", true, ""),
+            "Your `move` action was carried out."
+        );
+        let template = template.with_success("[{id}] {message} ({name})");
+        assert_eq!(template.render("1", "move", true, ""), "[1] (move)");
+        let template = template.with_failure("[{id}] {name} {failed}: {message}");
+        assert_eq!(
+            template.render("1", "move", false, "blocked"),
+            "[1] move {failed}: blocked"
+        );
+    }
+}
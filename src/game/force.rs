@@ -0,0 +1,145 @@
+//! Tracking the progress of action forces
+use std::{
+    borrow::Cow,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// The fallback called when an action force times out.
+pub(crate) type Fallback = Box<dyn FnOnce() + Send>;
+
+/// The state of an action force. See [`ForceHandle::status`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForceStatus {
+    /// Neuro hasn't successfully executed any of the forced actions yet.
+    Pending,
+    /// A successful result has been sent for one of the forced actions.
+    Completed,
+    /// The force has been cancelled via [`Api::cancel_force`](super::Api::cancel_force), or the
+    /// API has been reinitialized.
+    Cancelled,
+    /// The force has timed out (see
+    /// [`ForceActionsBuilder::with_timeout`](super::ForceActionsBuilder::with_timeout)).
+    TimedOut,
+}
+
+/// A handle for an action force, returned by
+/// [`ForceActionsBuilder::send`](super::ForceActionsBuilder::send). It can be cloned and queried
+/// at any time.
+///
+/// The handle is updated by the [`Session`](super::Session), so without a session it will stay
+/// [`ForceStatus::Pending`] forever.
+#[derive(Clone)]
+pub struct ForceHandle {
+    action_names: Arc<[Cow<'static, str>]>,
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    status: ForceStatus,
+    failed_attempts: u32,
+    deadline: Option<Instant>,
+    fallback: Option<Fallback>,
+}
+
+impl fmt::Debug for ForceHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state();
+        f.debug_struct("ForceHandle")
+            .field("action_names", &self.action_names)
+            .field("status", &state.status)
+            .field("failed_attempts", &state.failed_attempts)
+            .field("deadline", &state.deadline)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ForceHandle {
+    pub(crate) fn new(
+        action_names: Vec<Cow<'static, str>>,
+        timeout: Option<(Duration, Fallback)>,
+    ) -> Self {
+        let (deadline, fallback) = timeout.map_or((None, None), |(timeout, fallback)| {
+            (Some(Instant::now() + timeout), Some(fallback))
+        });
+        Self {
+            action_names: action_names.into(),
+            state: Arc::new(Mutex::new(State {
+                status: ForceStatus::Pending,
+                failed_attempts: 0,
+                deadline,
+                fallback,
+            })),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The names of the forced actions.
+    pub fn action_names(&self) -> &[Cow<'static, str>] {
+        &self.action_names
+    }
+
+    /// The current state of the force.
+    pub fn status(&self) -> ForceStatus {
+        self.state().status
+    }
+
+    /// Whether a successful result has been sent for one of the forced actions.
+    pub fn is_completed(&self) -> bool {
+        self.status() == ForceStatus::Completed
+    }
+
+    /// How many times Neuro tried to execute one of the forced actions and failed (this includes
+    /// the cases where her action data couldn't be deserialized).
+    pub fn failed_attempts(&self) -> u32 {
+        self.state().failed_attempts
+    }
+
+    /// Whether this is the same force as `other`.
+    pub(crate) fn same(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+    }
+
+    /// Record an action result. Returns `true` if the force is finished.
+    pub(crate) fn action_result(&self, name: &str, success: bool) -> bool {
+        if !self.action_names.iter().any(|x| x == name) {
+            return false;
+        }
+        let mut state = self.state();
+        if success {
+            state.status = ForceStatus::Completed;
+            state.fallback = None;
+            true
+        } else {
+            state.failed_attempts += 1;
+            false
+        }
+    }
+
+    /// Finish the force with the specified status (unless it's already finished), returning
+    /// whether it was still pending.
+    pub(crate) fn finish(&self, status: ForceStatus) -> bool {
+        let mut state = self.state();
+        if state.status != ForceStatus::Pending {
+            return false;
+        }
+        state.status = status;
+        state.fallback = None;
+        true
+    }
+
+    /// If the force has timed out, mark it as such and return the fallback.
+    pub(crate) fn check_timeout(&self, now: Instant) -> Option<Option<Fallback>> {
+        let mut state = self.state();
+        if state.status != ForceStatus::Pending || state.deadline.is_none_or(|x| x > now) {
+            return None;
+        }
+        state.status = ForceStatus::TimedOut;
+        Some(state.fallback.take())
+    }
+}
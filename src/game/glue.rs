@@ -1,6 +1,7 @@
 //! Just a bunch of boilerplate
 use std::{borrow::Cow, marker::PhantomData};
 
+use schemars::gen::SchemaSettings;
 use serde::{
     de::{EnumAccess, VariantAccess},
     Deserialize, Deserializer,
@@ -192,15 +193,27 @@ impl<'de, T: 'de + Deserialize<'de>> Actions<'de> for T {
 /// way.
 pub trait ActionMetadata {
     fn actions() -> Vec<schema::Action>;
+    /// Like [`ActionMetadata::actions`], but with the schemas generated using `settings`. The
+    /// default implementation ignores the settings.
+    fn actions_with(settings: &SchemaSettings) -> Vec<schema::Action> {
+        let _ = settings;
+        Self::actions()
+    }
     fn names() -> Vec<Cow<'static, str>>;
 }
 
 impl<T: Action> ActionMetadata for T {
     fn actions() -> Vec<schema::Action> {
+        Self::actions_with(&SchemaSettings::default())
+    }
+    fn actions_with(settings: &SchemaSettings) -> Vec<schema::Action> {
         vec![schema::Action {
             name: Self::name().into(),
             description: Self::description().into(),
-            schema: schemars::schema_for!(Self),
+            schema: settings
+                .clone()
+                .into_generator()
+                .into_root_schema_for::<Self>(),
         }]
     }
     fn names() -> Vec<Cow<'static, str>> {
@@ -211,10 +224,14 @@ macro_rules! tuple_actions {
     ($($a:tt),*) => {
         impl<$($a: Action),*> ActionMetadata for ($($a,)*) {
             fn actions() -> Vec<schema::Action> {
+                Self::actions_with(&SchemaSettings::default())
+            }
+            #[allow(unused_variables)]
+            fn actions_with(settings: &SchemaSettings) -> Vec<schema::Action> {
                 vec![$(schema::Action {
                     name: $a::name().into(),
                     description: $a::description().into(),
-                    schema: schemars::schema_for!($a),
+                    schema: settings.clone().into_generator().into_root_schema_for::<$a>(),
                 }),*]
             }
             fn names() -> Vec<Cow<'static, str>> {
@@ -0,0 +1,274 @@
+//! Limiting the size of outgoing messages
+use std::{borrow::Cow, fmt};
+
+use super::Error;
+use crate::schema::ClientCommandContents;
+
+/// What to do when an outgoing message exceeds one of the [`Limits`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LimitPolicy {
+    /// Truncate the text and append the truncation marker (see
+    /// [`Limits::with_truncation_marker`]). Registrations with too many actions are split into
+    /// multiple `actions/register` commands. Schemas can't be truncated, so too large schemas are
+    /// rejected like with [`LimitPolicy::Reject`].
+    #[default]
+    Truncate,
+    /// Don't send the command, and return [`Error::LimitExceeded`] instead.
+    Reject,
+    /// Log a warning, but send the command anyway.
+    Warn,
+}
+
+/// Which of the [`Limits`] was exceeded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LimitKind {
+    /// The length of a context message.
+    Context,
+    /// The length of an action force's state.
+    State,
+    /// The length of an action's description.
+    Description,
+    /// The size of an action's schema.
+    Schema,
+    /// The number of actions registered at once.
+    Actions,
+}
+
+impl fmt::Display for LimitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Context => "context message length",
+            Self::State => "action force state length",
+            Self::Description => "action description length",
+            Self::Schema => "action schema size",
+            Self::Actions => "number of actions per registration",
+        })
+    }
+}
+
+/// Limits on the size of outgoing messages, so that a game can't accidentally flood Neuro's
+/// context window (e.g. with a multi-megabyte action force state).
+///
+/// To use them, return them from [`Game::limits`](super::Game::limits). They're enforced when
+/// commands are sent, and what happens when a limit is exceeded is decided by the
+/// [`LimitPolicy`]. There are no limits by default.
+///
+/// Text lengths are measured in characters, and schema sizes are measured in bytes of serialized
+/// JSON.
+#[derive(Clone, Debug)]
+pub struct Limits {
+    context: Option<usize>,
+    state: Option<usize>,
+    description: Option<usize>,
+    schema: Option<usize>,
+    actions: Option<usize>,
+    policy: LimitPolicy,
+    marker: Cow<'static, str>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            context: None,
+            state: None,
+            description: None,
+            schema: None,
+            actions: None,
+            policy: LimitPolicy::default(),
+            marker: "… (truncated)".into(),
+        }
+    }
+}
+
+impl Limits {
+    /// Create limits that don't limit anything (yet).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the length of context messages.
+    #[must_use]
+    pub fn with_context_len(mut self, max: usize) -> Self {
+        self.context = Some(max);
+        self
+    }
+
+    /// Limit the length of action force states.
+    #[must_use]
+    pub fn with_state_len(mut self, max: usize) -> Self {
+        self.state = Some(max);
+        self
+    }
+
+    /// Limit the length of action descriptions.
+    #[must_use]
+    pub fn with_description_len(mut self, max: usize) -> Self {
+        self.description = Some(max);
+        self
+    }
+
+    /// Limit the size of action schemas.
+    #[must_use]
+    pub fn with_schema_size(mut self, max: usize) -> Self {
+        self.schema = Some(max);
+        self
+    }
+
+    /// Limit the number of actions registered by a single `actions/register` command.
+    #[must_use]
+    pub fn with_actions_per_registration(mut self, max: usize) -> Self {
+        self.actions = Some(max);
+        self
+    }
+
+    /// Set what happens when a limit is exceeded. The default is [`LimitPolicy::Truncate`].
+    #[must_use]
+    pub fn with_policy(mut self, policy: LimitPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Set the text appended to truncated text (it counts towards the limit). The default is
+    /// `"… (truncated)"`.
+    #[must_use]
+    pub fn with_truncation_marker(mut self, marker: impl Into<Cow<'static, str>>) -> Self {
+        self.marker = marker.into();
+        self
+    }
+
+    /// Enforce the limits on a command, returning the commands that should be sent instead.
+    pub(crate) fn apply(
+        &self,
+        cmd: ClientCommandContents,
+    ) -> Result<Vec<ClientCommandContents>, Error> {
+        Ok(match cmd {
+            ClientCommandContents::Context {
+                mut message,
+                silent,
+            } => {
+                self.text(LimitKind::Context, self.context, &mut message, None)?;
+                vec![ClientCommandContents::Context { message, silent }]
+            }
+            ClientCommandContents::ForceActions {
+                mut state,
+                query,
+                ephemeral_context,
+                action_names,
+            } => {
+                if let Some(state) = &mut state {
+                    self.text(LimitKind::State, self.state, state, None)?;
+                }
+                vec![ClientCommandContents::ForceActions {
+                    state,
+                    query,
+                    ephemeral_context,
+                    action_names,
+                }]
+            }
+            ClientCommandContents::RegisterActions { mut actions } => {
+                for action in &mut actions {
+                    self.text(
+                        LimitKind::Description,
+                        self.description,
+                        &mut action.description,
+                        Some(&action.name),
+                    )?;
+                    if let Some(max) = self.schema {
+                        // measured the same way it's going to be sent
+                        let len = crate::to_string(&action.schema)?.len();
+                        if len > max {
+                            self.exceeded(LimitKind::Schema, len, max, Some(&action.name), false)?;
+                        }
+                    }
+                }
+                match self.actions {
+                    Some(max) if actions.len() > max => {
+                        self.exceeded(LimitKind::Actions, actions.len(), max, None, true)?;
+                        if self.policy == LimitPolicy::Truncate {
+                            let chunk = max.max(1);
+                            let mut ret = Vec::new();
+                            while actions.len() > chunk {
+                                let rest = actions.split_off(chunk);
+                                ret.push(ClientCommandContents::RegisterActions { actions });
+                                actions = rest;
+                            }
+                            ret.push(ClientCommandContents::RegisterActions { actions });
+                            return Ok(ret);
+                        }
+                        vec![ClientCommandContents::RegisterActions { actions }]
+                    }
+                    _ => vec![ClientCommandContents::RegisterActions { actions }],
+                }
+            }
+            cmd => vec![cmd],
+        })
+    }
+
+    /// Enforce a text length limit on a text that belongs to `action` (if any).
+    fn text(
+        &self,
+        kind: LimitKind,
+        max: Option<usize>,
+        text: &mut Cow<'static, str>,
+        action: Option<&Cow<'static, str>>,
+    ) -> Result<(), Error> {
+        let Some(max) = max else {
+            return Ok(());
+        };
+        let len = text.chars().count();
+        if len <= max {
+            return Ok(());
+        }
+        self.exceeded(kind, len, max, action, true)?;
+        if self.policy == LimitPolicy::Truncate {
+            // the marker itself has to fit, too
+            let marker = prefix(&self.marker, max);
+            let keep = max - marker.chars().count();
+            let mut truncated = String::with_capacity(max);
+            truncated.push_str(prefix(text, keep));
+            truncated.push_str(marker);
+            *text = truncated.into();
+        }
+        Ok(())
+    }
+
+    /// Report an exceeded limit according to the policy (truncation is up to the caller).
+    fn exceeded(
+        &self,
+        kind: LimitKind,
+        len: usize,
+        max: usize,
+        action: Option<&Cow<'static, str>>,
+        truncatable: bool,
+    ) -> Result<(), Error> {
+        match self.policy {
+            LimitPolicy::Warn => {
+                log::warn!(
+                    "{kind}{} exceeds the limit ({len} > {max})",
+                    of_action(action)
+                );
+                Ok(())
+            }
+            LimitPolicy::Truncate if truncatable => Ok(()),
+            _ => Err(Error::LimitExceeded {
+                kind,
+                action: action.cloned(),
+                len,
+                max,
+            }),
+        }
+    }
+}
+
+/// `" of action `name`"`, or nothing if the limit isn't about a single action.
+pub(super) fn of_action(action: Option<&Cow<'static, str>>) -> String {
+    action.map_or_else(String::new, |x| format!(" of action `{x}`"))
+}
+
+/// The first `n` characters of the text.
+fn prefix(text: &str, n: usize) -> &str {
+    let end = text.char_indices().nth(n).map_or(text.len(), |(i, _)| i);
+    &text[..end]
+}
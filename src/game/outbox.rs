@@ -0,0 +1,111 @@
+//! Buffering commands while disconnected
+use std::borrow::Cow;
+
+use super::force::{ForceHandle, ForceStatus};
+use crate::schema::{self, ClientCommandContents};
+
+/// Queues commands while the connection is down, and sends them in a compacted form after the
+/// next [`Api::initialize`](super::Api::initialize) or [`Api::resume`](super::Api::resume).
+///
+/// To use it, add it to your [`Session`](super::Session) via
+/// [`Session::with_outbox`](super::Session::with_outbox). The session is considered disconnected
+/// after [`Game::try_send_command`](super::Game::try_send_command) fails (the command that
+/// failed is queued instead of returning an error), after
+/// [`Session::set_connected`](super::Session::set_connected) is used or after one of the
+/// ready-made [`client`](crate::client)s loses the connection, and connected again after
+/// [`Api::initialize`](super::Api::initialize) (or [`Api::resume`](super::Api::resume)).
+///
+/// When flushing the queue, which happens after `startup` is sent and the actions are registered
+/// again:
+///
+/// - Registered and unregistered actions are merged into a single `actions/register` command
+///   (unregistering is pointless after `startup`).
+/// - Only the last action force is sent, and only if all of its actions are registered. Other
+///   forces are considered stale, and their [`ForceHandle`]s are marked as cancelled.
+/// - Action results are dropped, since they're for actions from the previous connection.
+/// - Optionally, silent context messages are collapsed into one.
+#[derive(Clone, Debug, Default)]
+pub struct Outbox {
+    collapse_silent_context: bool,
+}
+
+impl Outbox {
+    /// Create the default outbox settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collapse all queued silent context messages into one (separated by newlines).
+    #[must_use]
+    pub fn with_collapse_silent_context(mut self, collapse_silent_context: bool) -> Self {
+        self.collapse_silent_context = collapse_silent_context;
+        self
+    }
+
+    /// Compact the queued commands.
+    pub(crate) fn compact(&self, queue: Vec<Queued>) -> Compacted {
+        let mut ret = Compacted::default();
+        let mut silent = Vec::new();
+        // where the collapsed silent context goes, i.e. where the first one was
+        let mut silent_at = 0;
+        for Queued { command, force } in queue {
+            match command {
+                ClientCommandContents::RegisterActions { actions } => {
+                    for action in actions {
+                        ret.register.retain(|x| x.name != action.name);
+                        ret.register.push(action);
+                    }
+                }
+                ClientCommandContents::UnregisterActions { action_names } => {
+                    ret.register.retain(|x| !action_names.contains(&x.name));
+                }
+                ClientCommandContents::Context {
+                    message,
+                    silent: true,
+                } if self.collapse_silent_context => {
+                    if silent.is_empty() {
+                        silent_at = ret.context.len();
+                    }
+                    silent.push(message);
+                }
+                ClientCommandContents::Context { message, silent } => {
+                    ret.context.push((message, silent));
+                }
+                ClientCommandContents::ForceActions { .. } => {
+                    if let Some((_, Some(force))) = ret.force.take() {
+                        force.finish(ForceStatus::Cancelled);
+                    }
+                    ret.force = Some((command, force));
+                }
+                ClientCommandContents::Startup | ClientCommandContents::ActionResult { .. } => {}
+                #[allow(unreachable_patterns)]
+                command => ret.other.push(command),
+            }
+        }
+        if !silent.is_empty() {
+            ret.context
+                .insert(silent_at, (silent.join("\n").into(), true));
+        }
+        ret
+    }
+}
+
+/// A command queued by the outbox.
+#[derive(Debug)]
+pub(crate) struct Queued {
+    pub command: ClientCommandContents,
+    /// The handle for an `actions/force` command.
+    pub force: Option<ForceHandle>,
+}
+
+/// The compacted outbox contents, in the order they should be sent.
+#[derive(Debug, Default)]
+pub(crate) struct Compacted {
+    pub register: Vec<schema::Action>,
+    pub context: Vec<(Cow<'static, str>, bool)>,
+    pub other: Vec<ClientCommandContents>,
+    pub force: Option<(ClientCommandContents, Option<ForceHandle>)>,
+    /// Whether `register` contains all actions registered before the reset, so
+    /// [`Game::reregister_actions`](super::Game::reregister_actions) shouldn't be called.
+    pub restored: bool,
+}
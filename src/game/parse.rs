@@ -0,0 +1,191 @@
+//! Parsing action data that isn't quite valid JSON
+use std::{borrow::Cow, fmt};
+
+use super::ActionError;
+
+/// A stage of the pipeline that parses incoming action data (see
+/// [`Game::parse_stages`](super::Game::parse_stages)).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseStage {
+    /// Parse the data as strict JSON.
+    Strict,
+    /// Parse the data as JSON5, which additionally allows comments, unquoted keys, single-quoted
+    /// strings, trailing commas and so on.
+    Json5,
+    /// Repair the data, then parse it as JSON5. This strips Markdown code fences, extracts the
+    /// first JSON object (or array) from the surrounding text, removes trailing commas, and
+    /// closes unterminated strings and brackets.
+    Repair,
+}
+
+impl fmt::Display for ParseStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Strict => "strict JSON",
+            Self::Json5 => "JSON5",
+            Self::Repair => "repair",
+        })
+    }
+}
+
+/// Try the stages in order, returning the first one that succeeded along with the text it
+/// parsed (which only differs from the data for [`ParseStage::Repair`]). If all of them fail,
+/// the first stage's error is returned, since the later ones are merely fallbacks.
+pub(crate) fn run<'a>(
+    stages: &[ParseStage],
+    data: &'a str,
+) -> Result<(ParseStage, Cow<'a, str>), ActionError> {
+    let stages = if stages.is_empty() {
+        &[ParseStage::Json5]
+    } else {
+        stages
+    };
+    let mut first = None;
+    for &stage in stages {
+        let res = match stage {
+            ParseStage::Strict => serde_json::from_str::<serde::de::IgnoredAny>(data)
+                .map(|_| Cow::Borrowed(data))
+                .map_err(ActionError::strict_syntax),
+            ParseStage::Json5 => json5::Deserializer::from_str(data)
+                .map(|_| Cow::Borrowed(data))
+                .map_err(ActionError::syntax),
+            ParseStage::Repair => match repair(data) {
+                Some(text) => json5::Deserializer::from_str(&text)
+                    .map(|_| ())
+                    .map_err(ActionError::syntax)
+                    .map(|()| Cow::Owned(text)),
+                None => Err(ActionError::Syntax {
+                    message: "no JSON object or array found".to_owned(),
+                    location: None,
+                }),
+            },
+        };
+        match res {
+            Ok(text) => return Ok((stage, text)),
+            Err(err) => {
+                first.get_or_insert(err);
+            }
+        }
+    }
+    Err(first.expect("there's at least one stage"))
+}
+
+/// Strip Markdown code fences around the text, if any.
+fn strip_fences(text: &str) -> &str {
+    let Some((_, rest)) = text.split_once("```") else {
+        return text;
+    };
+    // skip the info string (e.g. "json") unless the code is on the same line
+    let rest = match rest.split_once('\n') {
+        Some((info, code)) if !info.contains(['{', '[']) => code,
+        _ => rest,
+    };
+    rest.split_once("```").map_or(rest, |(code, _)| code)
+}
+
+/// Extract the first JSON object or array from the text and fix it up as far as possible.
+pub(crate) fn repair(text: &str) -> Option<String> {
+    let text = strip_fences(text);
+    let start = text.find(['{', '['])?;
+    let mut ret = String::with_capacity(text.len() - start);
+    // the closing brackets of the currently open objects and arrays
+    let mut stack = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    for c in text[start..].chars() {
+        if let Some(q) = quote {
+            ret.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => {
+                quote = Some(c);
+                ret.push(c);
+            }
+            '{' => {
+                stack.push('}');
+                ret.push(c);
+            }
+            '[' => {
+                stack.push(']');
+                ret.push(c);
+            }
+            // stray closing brackets are dropped
+            '}' | ']' if stack.contains(&c) => {
+                while let Some(close) = stack.pop() {
+                    strip_trailing_comma(&mut ret);
+                    ret.push(close);
+                    if close == c {
+                        break;
+                    }
+                }
+                if stack.is_empty() {
+                    return Some(ret);
+                }
+            }
+            '}' | ']' => {}
+            c => ret.push(c),
+        }
+    }
+    if let Some(q) = quote {
+        if escaped {
+            ret.pop();
+        }
+        ret.push(q);
+    }
+    while let Some(close) = stack.pop() {
+        strip_trailing_comma(&mut ret);
+        ret.push(close);
+    }
+    Some(ret)
+}
+
+fn strip_trailing_comma(text: &mut String) {
+    let trimmed = text.trim_end();
+    if let Some(trimmed) = trimmed.strip_suffix(',') {
+        text.truncate(trimmed.len());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{repair, run, ParseStage};
+
+    #[test]
+    fn test_repair() {
+        assert_eq!(
+            repair("Sure! Here you go:\n```json\n{\"x\": 1, \"y\": [1, 2,],}\n```\nHave fun!")
+                .as_deref(),
+            Some("{\"x\": 1, \"y\": [1, 2]}")
+        );
+        assert_eq!(
+            repair("I'll move to {\"x\": 1, \"y\": {\"z\": \"a}").as_deref(),
+            Some("{\"x\": 1, \"y\": {\"z\": \"a}\"}}")
+        );
+        assert_eq!(
+            repair("{\"x\": [1, 2}}").as_deref(),
+            Some("{\"x\": [1, 2]}")
+        );
+        assert_eq!(repair("no JSON here"), None);
+    }
+
+    #[test]
+    fn test_run() {
+        let stages = [ParseStage::Strict, ParseStage::Json5, ParseStage::Repair];
+        assert_eq!(run(&stages, "{\"x\": 1}").unwrap().0, ParseStage::Strict);
+        assert_eq!(run(&stages, "{x: 1}").unwrap().0, ParseStage::Json5);
+        assert_eq!(
+            run(&stages, "```\n{x: 1\n```").unwrap(),
+            (ParseStage::Repair, "{x: 1\n}".into())
+        );
+        assert!(run(&stages[..2], "```\n{x: 1\n```").is_err());
+    }
+}
@@ -0,0 +1,587 @@
+//! Optional client-side session state
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Instant,
+};
+
+use super::{
+    force::{Fallback, ForceHandle, ForceStatus},
+    outbox::{Outbox, Queued},
+    watchdog::Watchdog,
+};
+use crate::schema;
+
+/// Client-side state that mirrors what Neuro currently knows about the game.
+///
+/// This is entirely opt-in: to use it, store a session in your game object and return a clone of
+/// it from [`Game::session`](super::Game::session) (it's a cheap reference-counted handle). The
+/// [`Api`](super::Api) methods will then keep it up to date, which allows them to skip redundant
+/// messages (e.g. registering an action that's already registered) and lets you query the
+/// current state.
+///
+/// The session is reset by [`Api::initialize`](super::Api::initialize), so you should call it
+/// whenever the connection is reopened, as usual (or [`Api::resume`](super::Api::resume), to
+/// register the actions that were registered before again).
+#[derive(Clone, Debug, Default)]
+pub struct Session {
+    state: Arc<Mutex<State>>,
+}
+
+/// How many successfully answered actions are remembered for reporting their execution.
+const MAX_EXECUTING: usize = 64;
+
+#[derive(Debug)]
+struct State {
+    registered: Vec<schema::Action>,
+    /// The action that's currently being handled. There's only one, since messages are handled
+    /// one at a time (even with `AsyncApi`, handlers must not run concurrently).
+    current: Option<Current>,
+    /// Deferred actions that haven't been answered yet.
+    pending: Vec<Pending>,
+    /// Ids and names of recent successfully answered actions, whose execution can still be
+    /// reported.
+    executing: VecDeque<(String, String)>,
+    /// Action forces that are still pending.
+    forces: Vec<ForceHandle>,
+    /// Names of actions from cancelled forces that should be rejected until they're registered
+    /// again.
+    rejected: Vec<Cow<'static, str>>,
+    watchdog: Option<Watchdog>,
+    outbox: Option<Outbox>,
+    /// Commands queued while disconnected.
+    queue: Vec<Queued>,
+    connected: bool,
+    /// Whether the next reset should restore the registered actions, see [`Session::restore`].
+    restore: bool,
+    coerce: bool,
+    #[cfg(feature = "validation")]
+    validate: bool,
+    /// Compiled schemas of registered actions (`None` if the schema couldn't be compiled).
+    #[cfg(feature = "validation")]
+    validators: Vec<(String, Option<jsonschema::Validator>)>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            registered: Vec::new(),
+            current: None,
+            pending: Vec::new(),
+            executing: VecDeque::new(),
+            forces: Vec::new(),
+            rejected: Vec::new(),
+            watchdog: None,
+            outbox: None,
+            queue: Vec::new(),
+            connected: true,
+            restore: false,
+            coerce: false,
+            #[cfg(feature = "validation")]
+            validate: false,
+            #[cfg(feature = "validation")]
+            validators: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Current {
+    id: String,
+    name: String,
+    started: Instant,
+    deferred: bool,
+}
+
+#[derive(Debug)]
+struct Pending {
+    id: String,
+    name: String,
+    started: Instant,
+    /// Whether the watchdog has already reported this action.
+    reported: bool,
+}
+
+impl State {
+    fn warn_pending(&self) {
+        for Pending { id, name, .. } in &self.pending {
+            log::warn!("the result for action `{name}` (id `{id}`) was deferred, but never sent");
+        }
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        self.warn_pending();
+    }
+}
+
+/// A token for an action whose result will be sent later via
+/// [`Api::send_action_result`](super::Api::send_action_result). See
+/// [`Api::defer_action_result`](super::Api::defer_action_result).
+#[must_use = "the action result has to be sent later using this action's id"]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingAction {
+    id: String,
+}
+
+impl PendingAction {
+    /// The action's id.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Convert the token into the action's id.
+    pub fn into_id(self) -> String {
+        self.id
+    }
+}
+
+impl Session {
+    /// Create a new session.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Watch how long it takes to answer actions, see [`Watchdog`].
+    #[must_use]
+    pub fn with_watchdog(self, watchdog: Watchdog) -> Self {
+        self.state().watchdog = Some(watchdog);
+        self
+    }
+
+    /// Queue commands while disconnected, see [`Outbox`].
+    #[must_use]
+    pub fn with_outbox(self, outbox: Outbox) -> Self {
+        self.state().outbox = Some(outbox);
+        self
+    }
+
+    /// Coerce incoming action data to match the schema the action was registered with where
+    /// it's safe to do so, e.g. `"5"` or `5.0` for integers, `"True"` for booleans, or the wrong
+    /// case for enum variants. The applied coercions are passed to
+    /// [`Game::action_coerced`](super::Game::action_coerced).
+    ///
+    /// Coerced data is deserialized from an owned [`serde_json::Value`], so actions with fields
+    /// borrowed from the data (e.g. `&str`, or `Cow<str>` with `#[serde(borrow)]`) fail to
+    /// deserialize if anything was coerced, even though borrowing works otherwise (with
+    /// [`ParseStage::Strict`](super::ParseStage::Strict)).
+    #[must_use]
+    pub fn with_coercion(self, coerce: bool) -> Self {
+        self.state().coerce = coerce;
+        self
+    }
+
+    /// Validate incoming action data against the schema the action was registered with, and
+    /// answer with a failure result that describes the problems if it doesn't match (without
+    /// calling [`Game::handle_action`](super::Game::handle_action)).
+    ///
+    /// This catches constraints that aren't checked when deserializing the action, e.g.
+    /// `minimum`, `maxLength`, `enum` or `pattern` from custom
+    /// [`JsonSchema`](schemars::JsonSchema) impls.
+    #[cfg(feature = "validation")]
+    #[must_use]
+    pub fn with_validation(self, validate: bool) -> Self {
+        self.state().validate = validate;
+        self
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// All actions that are currently registered, in the order of registration.
+    pub fn registered(&self) -> Vec<schema::Action> {
+        self.state().registered.clone()
+    }
+
+    /// The names of all actions that are currently registered, in the order of registration.
+    pub fn registered_names(&self) -> Vec<Cow<'static, str>> {
+        self.state()
+            .registered
+            .iter()
+            .map(|x| x.name.clone())
+            .collect()
+    }
+
+    /// Whether an action with this name is currently registered.
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.state().registered.iter().any(|x| x.name == name)
+    }
+
+    /// Ids of the actions whose results have been deferred, but not sent yet.
+    pub fn pending_actions(&self) -> Vec<String> {
+        self.state().pending.iter().map(|x| x.id.clone()).collect()
+    }
+
+    /// Action forces that haven't been completed, cancelled or timed out yet.
+    pub fn pending_forces(&self) -> Vec<ForceHandle> {
+        self.state().forces.clone()
+    }
+
+    /// Mark the connection as up or down. While it's down, commands are queued by the
+    /// [`Outbox`] (if there is one). [`Api::initialize`](super::Api::initialize) marks the
+    /// connection as up.
+    pub fn set_connected(&self, connected: bool) {
+        self.state().connected = connected;
+    }
+
+    /// Whether the connection is considered to be up.
+    pub fn is_connected(&self) -> bool {
+        self.state().connected
+    }
+
+    /// The commands queued by the [`Outbox`], in the order they were sent (before compaction).
+    pub fn queued_commands(&self) -> Vec<schema::ClientCommandContents> {
+        self.state()
+            .queue
+            .iter()
+            .map(|x| x.command.clone())
+            .collect()
+    }
+
+    /// Queue the command if there's an outbox and the connection is down, otherwise return it
+    /// back.
+    pub(crate) fn enqueue(
+        &self,
+        command: schema::ClientCommandContents,
+    ) -> Option<schema::ClientCommandContents> {
+        let mut state = self.state();
+        if state.outbox.is_none() || state.connected {
+            return Some(command);
+        }
+        state.queue.push(Queued {
+            command,
+            force: None,
+        });
+        None
+    }
+
+    /// Mark the connection as down after failing to send the command, and queue it. Returns
+    /// the command back if there's no outbox.
+    pub(crate) fn send_failed(
+        &self,
+        command: schema::ClientCommandContents,
+    ) -> Option<schema::ClientCommandContents> {
+        self.state().connected = false;
+        self.enqueue(command)
+    }
+
+    /// Make the next reset restore the currently registered actions instead of forgetting them.
+    pub(crate) fn restore(&self) {
+        self.state().restore = true;
+    }
+
+    /// Forget about all registered actions, the action being handled, pending action results and
+    /// executions that could still be reported (this happens when `startup` is sent), mark the connection as up and return the outbox contents that should
+    /// be sent after `startup`.
+    pub(crate) fn reset(&self) -> Option<super::outbox::Compacted> {
+        let mut state = self.state();
+        state.connected = true;
+        let queue = std::mem::take(&mut state.queue);
+        let mut compacted = state.outbox.as_ref().map(|x| x.compact(queue));
+        if std::mem::take(&mut state.restore) {
+            // the session already reflects the actions (un)registered while disconnected
+            let compacted = compacted.get_or_insert_with(Default::default);
+            compacted.register = std::mem::take(&mut state.registered);
+            compacted.restored = true;
+        }
+        state.registered.clear();
+        #[cfg(feature = "validation")]
+        state.validators.clear();
+        state.current = None;
+        state.warn_pending();
+        state.pending.clear();
+        state.executing.clear();
+        for force in state.forces.drain(..) {
+            force.finish(ForceStatus::Cancelled);
+        }
+        state.rejected.clear();
+        compacted
+    }
+
+    /// Mark the start of handling an action.
+    pub(crate) fn begin_action(&self, id: &str, name: &str) {
+        self.state().current = Some(Current {
+            id: id.to_owned(),
+            name: name.to_owned(),
+            started: Instant::now(),
+            deferred: false,
+        });
+    }
+
+    /// The id of the action that's currently being handled, if any.
+    pub(crate) fn current_id(&self) -> Option<String> {
+        self.state().current.as_ref().map(|x| x.id.clone())
+    }
+
+    /// Record that an action was answered successfully, so its execution can be reported.
+    pub(crate) fn answered(&self, id: &str, name: &str) {
+        let mut state = self.state();
+        if state.executing.len() >= MAX_EXECUTING {
+            state.executing.pop_front();
+        }
+        state.executing.push_back((id.to_owned(), name.to_owned()));
+    }
+
+    /// Mark the execution of an action as reported, returning its name, or `None` if it wasn't
+    /// answered successfully (or was already reported).
+    pub(crate) fn executed(&self, id: &str) -> Option<String> {
+        let mut state = self.state();
+        let i = state.executing.iter().position(|(x, _)| x == id)?;
+        state.executing.remove(i).map(|(_, name)| name)
+    }
+
+    /// Mark the end of handling an action at `now`, and return whether its result has been
+    /// deferred.
+    pub(crate) fn end_action(&self, now: Instant) -> bool {
+        let mut state = self.state();
+        let Some(current) = state.current.take() else {
+            return false;
+        };
+        if current.deferred {
+            return true;
+        }
+        let watchdog = state.watchdog.clone();
+        drop(state);
+        if let Some(watchdog) = watchdog {
+            let elapsed = now.saturating_duration_since(current.started);
+            if elapsed > watchdog.threshold() {
+                watchdog.report(&current.id, &current.name, elapsed);
+            }
+        }
+        false
+    }
+
+    /// Defer the result of the action that's currently being handled, if any.
+    pub(crate) fn defer(&self) -> Option<PendingAction> {
+        let mut state = self.state();
+        let state = &mut *state;
+        let current = state.current.as_mut()?;
+        if !current.deferred {
+            current.deferred = true;
+            state.pending.push(Pending {
+                id: current.id.clone(),
+                name: current.name.clone(),
+                started: current.started,
+                reported: false,
+            });
+        }
+        Some(PendingAction {
+            id: current.id.clone(),
+        })
+    }
+
+    /// Mark a deferred action as answered, returning its name, or `None` if it isn't awaiting a
+    /// result.
+    pub(crate) fn resolve(&self, id: &str) -> Option<String> {
+        let mut state = self.state();
+        let i = state.pending.iter().position(|x| x.id == id)?;
+        let pending = state.pending.remove(i);
+        let watchdog = state.watchdog.clone().filter(|_| !pending.reported);
+        drop(state);
+        if let Some(watchdog) = watchdog {
+            let elapsed = pending.started.elapsed();
+            if elapsed > watchdog.threshold() {
+                watchdog.report(&pending.id, &pending.name, elapsed);
+            }
+        }
+        Some(pending.name)
+    }
+
+    /// Report the deferred actions that exceeded the watchdog's threshold, and return the ids and
+    /// names of the ones that exceeded its deadline (they're no longer considered pending).
+    pub(crate) fn overdue(&self, now: Instant) -> Vec<(String, String)> {
+        let mut state = self.state();
+        let Some(watchdog) = state.watchdog.clone() else {
+            return Vec::new();
+        };
+        let mut slow = Vec::new();
+        let mut overdue = Vec::new();
+        state.pending.retain_mut(|pending| {
+            let elapsed = now.saturating_duration_since(pending.started);
+            if watchdog.deadline().is_some_and(|x| elapsed > x) {
+                overdue.push((pending.id.clone(), pending.name.clone()));
+                return false;
+            }
+            if !pending.reported && elapsed > watchdog.threshold() {
+                pending.reported = true;
+                slow.push((pending.id.clone(), pending.name.clone(), elapsed));
+            }
+            true
+        });
+        drop(state);
+        for (id, name, elapsed) in slow {
+            watchdog.report(&id, &name, elapsed);
+        }
+        overdue
+    }
+
+    /// Record that a result is being sent for an action.
+    pub(crate) fn action_result(&self, name: &str, success: bool) {
+        self.state()
+            .forces
+            .retain(|force| !force.action_result(name, success));
+    }
+
+    /// Whether the action belongs to a cancelled force and should be rejected.
+    pub(crate) fn is_rejected(&self, name: &str) -> bool {
+        self.state().rejected.iter().any(|x| x == name)
+    }
+
+    /// Start tracking an action force.
+    pub(crate) fn add_force(&self, force: ForceHandle) {
+        let mut state = self.state();
+        // if the force has been queued, it will be tracked once it's actually sent
+        if let Some(queued) = state.queue.last_mut().filter(|x| {
+            x.force.is_none()
+                && matches!(
+                    x.command,
+                    schema::ClientCommandContents::ForceActions { .. }
+                )
+        }) {
+            queued.force = Some(force);
+        } else {
+            state.forces.push(force);
+        }
+    }
+
+    /// Stop tracking a cancelled or timed out action force, and reject its actions from now on.
+    pub(crate) fn remove_force(&self, force: &ForceHandle) {
+        let mut state = self.state();
+        state.forces.retain(|x| !x.same(force));
+        for name in force.action_names() {
+            if !state.rejected.contains(name) {
+                state.rejected.push(name.clone());
+            }
+        }
+    }
+
+    /// Find the action forces that have timed out, returning them along with their fallbacks.
+    pub(crate) fn timed_out(&self, now: Instant) -> Vec<(ForceHandle, Option<Fallback>)> {
+        let forces = self.state().forces.clone();
+        forces
+            .into_iter()
+            .filter_map(|force| {
+                let fallback = force.check_timeout(now)?;
+                self.remove_force(&force);
+                Some((force, fallback))
+            })
+            .collect()
+    }
+
+    /// The schema of a registered action.
+    pub(crate) fn schema(&self, name: &str) -> Option<schemars::schema::RootSchema> {
+        let state = self.state();
+        let action = state.registered.iter().find(|x| x.name == name)?;
+        Some(action.schema.clone())
+    }
+
+    /// Whether incoming action data has to be parsed into a value, for coercing or validating it.
+    pub(crate) fn inspects_data(&self) -> bool {
+        let state = self.state();
+        #[cfg(feature = "validation")]
+        if state.validate {
+            return true;
+        }
+        state.coerce
+    }
+
+    /// Coerce action data to match the action's registered schema, returning the coercions that
+    /// were applied.
+    pub(crate) fn coerce(&self, name: &str, data: &mut serde_json::Value) -> Vec<super::Coercion> {
+        let state = self.state();
+        if !state.coerce {
+            return Vec::new();
+        }
+        match state.registered.iter().find(|x| x.name == name) {
+            Some(action) => super::coerce::coerce(&action.schema, data),
+            None => Vec::new(),
+        }
+    }
+
+    /// Validate action data against the action's registered schema, returning the paths of the
+    /// offending fields along with the problems if it doesn't match.
+    #[cfg(feature = "validation")]
+    pub(crate) fn validate(
+        &self,
+        name: &str,
+        data: &serde_json::Value,
+    ) -> Option<Vec<(String, String)>> {
+        let mut state = self.state();
+        if !state.validate {
+            return None;
+        }
+        let i = match state.validators.iter().position(|(x, _)| x == name) {
+            Some(i) => i,
+            None => {
+                let schema = &state.registered.iter().find(|x| x.name == name)?.schema;
+                let validator = serde_json::to_value(schema)
+                    .map_err(|err| err.to_string())
+                    .and_then(|x| {
+                        // `$schema` is stripped when registering, but `schemars` generates draft 7
+                        // schemas (e.g. with the pre-2020-12 tuple syntax)
+                        jsonschema::options()
+                            .with_draft(jsonschema::Draft::Draft7)
+                            .build(&x)
+                            .map_err(|err| err.to_string())
+                    })
+                    .map_err(|err| {
+                        log::warn!("failed to compile the schema of action `{name}`: {err}")
+                    })
+                    .ok();
+                state.validators.push((name.to_owned(), validator));
+                state.validators.len() - 1
+            }
+        };
+        let validator = state.validators[i].1.as_ref()?;
+        let errors = validator
+            .iter_errors(data)
+            .map(|err| {
+                let path = super::action_error::pointer_to_path(&err.instance_path.to_string());
+                (path, err.to_string())
+            })
+            .collect::<Vec<_>>();
+        (!errors.is_empty()).then_some(errors)
+    }
+
+    /// Record the actions as registered, and return the ones that weren't registered already.
+    pub(crate) fn register(&self, actions: Vec<schema::Action>) -> Vec<schema::Action> {
+        let mut state = self.state();
+        let mut ret = Vec::with_capacity(actions.len());
+        for action in actions {
+            if state.registered.iter().any(|x| x.name == action.name) {
+                log::warn!(
+                    "action `{}` is already registered, not registering it again",
+                    action.name
+                );
+            } else {
+                state.rejected.retain(|x| *x != action.name);
+                state.registered.push(action.clone());
+                ret.push(action);
+            }
+        }
+        ret
+    }
+
+    /// Record the actions as unregistered, and return the ones that were actually registered.
+    pub(crate) fn unregister(
+        &self,
+        action_names: Vec<Cow<'static, str>>,
+    ) -> Vec<Cow<'static, str>> {
+        let mut state = self.state();
+        let mut ret = Vec::with_capacity(action_names.len());
+        for name in action_names {
+            if let Some(i) = state.registered.iter().position(|x| x.name == name) {
+                state.registered.remove(i);
+                #[cfg(feature = "validation")]
+                state.validators.retain(|(x, _)| *x != name);
+                ret.push(name);
+            } else {
+                log::warn!("action `{name}` is not registered, not unregistering it");
+            }
+        }
+        ret
+    }
+}
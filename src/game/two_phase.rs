@@ -0,0 +1,243 @@
+//! Splitting action handling into validation and execution
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use super::{AsyncGame, Game, GameMut};
+
+/// Validated actions waiting to be executed by the game loop (see [`TwoPhaseGame`]).
+#[derive(Debug)]
+pub struct ActionQueue<T> {
+    queue: Mutex<VecDeque<T>>,
+}
+
+impl<T> Default for ActionQueue<T> {
+    fn default() -> Self {
+        Self {
+            queue: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+impl<T> ActionQueue<T> {
+    /// Create an empty queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn queue(&self) -> MutexGuard<'_, VecDeque<T>> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Add an action to the end of the queue.
+    pub fn push(&self, action: T) {
+        self.queue().push_back(action);
+    }
+
+    /// Take the action at the front of the queue.
+    pub fn pop(&self) -> Option<T> {
+        self.queue().pop_front()
+    }
+
+    /// The number of queued actions.
+    pub fn len(&self) -> usize {
+        self.queue().len()
+    }
+
+    /// Whether there are no queued actions.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop all queued actions (e.g. when the round they were meant for is over).
+    pub fn clear(&self) {
+        self.queue().clear();
+    }
+}
+
+/// An optional extension of [`Game`] that splits handling actions into two steps, following the
+/// flow recommended by the API: validate the action and send the result right away, then
+/// execute it in-game.
+///
+/// [`TwoPhaseGame::validate`] runs inside [`Api::handle_message`](super::Api::handle_message)
+/// and decides the `action/result` that is sent to Neuro. Validated actions are added to the
+/// [`ActionQueue`], and executed via [`TwoPhaseGame::execute`] when the game loop calls
+/// [`TwoPhaseGame::execute_queued`]. If the execution fails, you can let Neuro know via
+/// [`Api::report_execution`](super::Api::report_execution) (to do that, keep the result of
+/// [`Api::current_action_id`](super::Api::current_action_id) in the validated action).
+///
+/// To use it, put `#[neuro_sama::derive::two_phase]` on your [`Game`] impl, which implements
+/// [`Game::handle_action`] by calling [`TwoPhaseGame::validate_action`] (this works for
+/// [`GameMut`] and [`AsyncGame`] impls as well):
+///
+/// ```
+/// # use neuro_sama::game::{ActionQueue, Game, TwoPhaseGame};
+/// # #[derive(Debug, schemars::JsonSchema, serde::Deserialize)]
+/// # struct Move { x: u32 }
+/// # #[derive(neuro_sama::derive::Actions)]
+/// # enum Action {
+/// #     /// Move
+/// #     #[name = "move"]
+/// #     Move(Move),
+/// # }
+/// struct MyGame {
+///     width: u32,
+///     queue: ActionQueue<u32>,
+/// }
+///
+/// #[neuro_sama::derive::two_phase]
+/// impl Game for MyGame {
+///     const NAME: &'static str = "My Game";
+///     type Actions<'a> = Action;
+///     fn reregister_actions(&self) {}
+///     fn send_command(&self, _message: tungstenite::Message) {}
+/// }
+///
+/// impl TwoPhaseGame for MyGame {
+///     type Validated = u32;
+///     fn validate(&self, action: &Action) -> Result<(u32, Option<String>), String> {
+///         match action {
+///             Action::Move(Move { x }) if *x < self.width => Ok((*x, None)),
+///             Action::Move(_) => Err(format!("x must be less than {}", self.width)),
+///         }
+///     }
+///     fn execute(&self, x: u32) {
+///         // move the player to `x`
+///     }
+///     fn action_queue(&self) -> &ActionQueue<u32> {
+///         &self.queue
+///     }
+/// }
+///
+/// # let game = MyGame { width: 3, queue: ActionQueue::new() };
+/// // in the game loop:
+/// game.execute_queued();
+/// ```
+#[neuro_sama_derive::generic_asyncness(AsyncTwoPhaseGame, AsyncGame)]
+#[neuro_sama_derive::generic_mutability(TwoPhaseGameMut, GameMut)]
+pub trait TwoPhaseGame: Game {
+    /// A validated action, with everything needed to execute it.
+    type Validated;
+
+    /// Validate Neuro's action, returning the validated action along with an optional message
+    /// for the successful result. If this returns `Err`, the action is rejected with the given
+    /// message (and retried by Neuro if it was forced).
+    fn validate(
+        &self,
+        action: &Self::Actions<'_>,
+    ) -> Result<(Self::Validated, Option<String>), String>;
+
+    /// Execute a validated action in-game.
+    fn execute(&self, validated: Self::Validated);
+
+    /// The queue validated actions wait in until they're executed.
+    fn action_queue(&self) -> &ActionQueue<Self::Validated>;
+
+    /// Validate the action and queue it for execution if it's valid. This is what
+    /// `#[neuro_sama::derive::two_phase]` implements [`Game::handle_action`] with.
+    fn validate_action(&self, action: Self::Actions<'_>) -> Result<Option<String>, Option<String>> {
+        let (validated, message) = self.validate(&action).map_err(Some)?;
+        self.action_queue().push(validated);
+        Ok(message)
+    }
+
+    /// Execute the queued actions in order, returning how many were executed. Call this from
+    /// the game loop.
+    fn execute_queued(&self) -> usize {
+        let mut count = 0;
+        while let Some(validated) = self.action_queue().pop() {
+            self.execute(validated);
+            count += 1;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+
+    use serde::Deserialize;
+
+    use super::{ActionQueue, TwoPhaseGame};
+    use crate::{
+        self as neuro_sama,
+        game::{Api, Game},
+    };
+
+    /// Move action
+    #[derive(Debug, schemars::JsonSchema, Deserialize)]
+    struct Move {
+        x: u32,
+    }
+
+    #[derive(crate::derive::Actions, Debug)]
+    enum Action {
+        /// test
+        #[name = "move"]
+        Move(Move),
+    }
+
+    #[derive(Default)]
+    struct TestGame {
+        sent: RefCell<Vec<String>>,
+        position: RefCell<Vec<u32>>,
+        queue: ActionQueue<u32>,
+    }
+
+    #[crate::derive::two_phase]
+    impl Game for TestGame {
+        const NAME: &'static str = "Test";
+        type Actions<'a> = Action;
+        fn reregister_actions(&self) {}
+        fn send_command(&self, message: tungstenite::Message) {
+            self.sent.borrow_mut().push(message.into_text().unwrap());
+        }
+    }
+
+    impl TwoPhaseGame for TestGame {
+        type Validated = u32;
+        fn validate(&self, action: &Action) -> Result<(u32, Option<String>), String> {
+            let Action::Move(Move { x }) = action;
+            if *x == 0 {
+                Ok((*x, Some("already there".to_owned())))
+            } else if *x < 3 {
+                Ok((*x, None))
+            } else {
+                Err("out of bounds".to_owned())
+            }
+        }
+        fn execute(&self, x: u32) {
+            self.position.borrow_mut().push(x);
+        }
+        fn action_queue(&self) -> &ActionQueue<u32> {
+            &self.queue
+        }
+    }
+
+    #[test]
+    fn test_two_phase() {
+        let game = TestGame::default();
+        for (id, x) in [(1, 1), (2, 5), (3, 2), (4, 0)] {
+            game.handle_message(tungstenite::Message::text(format!(
+                r#"{{"command":"action","data":{{"id":"{id}","name":"move","data":"{{\"x\":{x}}}"}}}}"#
+            )))
+            .unwrap();
+        }
+        assert_eq!(
+            *game.sent.borrow(),
+            [
+                r#"{"command":"action/result","data":{"id":"1","success":true,"message":null},"game":"Test"}"#,
+                r#"{"command":"action/result","data":{"id":"2","success":false,"message":"out of bounds"},"game":"Test"}"#,
+                r#"{"command":"action/result","data":{"id":"3","success":true,"message":null},"game":"Test"}"#,
+                r#"{"command":"action/result","data":{"id":"4","success":true,"message":"already there"},"game":"Test"}"#,
+            ]
+        );
+        // nothing is executed until the game loop gets to it
+        assert!(game.position.borrow().is_empty());
+        assert_eq!(game.execute_queued(), 3);
+        assert_eq!(*game.position.borrow(), [1, 2, 0]);
+        assert!(game.queue.is_empty());
+    }
+}
@@ -0,0 +1,84 @@
+//! Measuring how long it takes to answer actions
+use std::{fmt, sync::Arc, time::Duration};
+
+/// The callback called with the action's id, name and how long it has been waiting.
+type Callback = dyn Fn(&str, &str, Duration) + Send + Sync;
+
+/// Watches how long it takes for the game to send an `action/result` after receiving an action.
+/// Neuro is blocked until the result arrives, so it should be sent as soon as possible.
+///
+/// To use it, add it to your [`Session`](super::Session) via
+/// [`Session::with_watchdog`](super::Session::with_watchdog). Deferred action results (see
+/// [`Api::defer_action_result`](super::Api::defer_action_result)) are checked by
+/// [`Api::tick`](super::Api::tick), so you should call it periodically.
+#[derive(Clone)]
+pub struct Watchdog {
+    threshold: Duration,
+    callback: Option<Arc<Callback>>,
+    deadline: Option<Duration>,
+}
+
+impl fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("threshold", &self.threshold)
+            .field("deadline", &self.deadline)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Watchdog {
+    /// Create a watchdog that logs a warning when answering an action takes longer than
+    /// `threshold`.
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            callback: None,
+            deadline: None,
+        }
+    }
+
+    /// Call `callback` instead of logging a warning when the threshold is exceeded. The
+    /// callback's arguments are the action's id, its name and how long it has been waiting for
+    /// a result so far.
+    #[must_use]
+    pub fn with_callback(
+        mut self,
+        callback: impl 'static + Send + Sync + Fn(&str, &str, Duration),
+    ) -> Self {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Automatically send a failure result for deferred actions that haven't been answered after
+    /// `deadline`, so that Neuro doesn't hang. Sending the result afterwards will fail with
+    /// [`Error::UnexpectedActionResult`](super::Error::UnexpectedActionResult).
+    ///
+    /// This is done by [`Api::tick`](super::Api::tick), and can't interrupt
+    /// [`Game::handle_action`](super::Game::handle_action) itself.
+    #[must_use]
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub(crate) fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    pub(crate) fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    /// Report an action that took too long.
+    pub(crate) fn report(&self, id: &str, name: &str, elapsed: Duration) {
+        if let Some(callback) = &self.callback {
+            callback(id, name, elapsed);
+        } else {
+            log::warn!(
+                "action `{name}` (id `{id}`) has been waiting for a result for {elapsed:?}, \
+                 Neuro is blocked until it gets one"
+            );
+        }
+    }
+}
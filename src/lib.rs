@@ -1,7 +1,7 @@
 //! A crate implementing the [Neuro-sama game API](https://github.com/VedalAI/neuro-game-sdk/).
 //!
-//! You will have to bring your own IO (i.e. work with the `tungstenite` or `tokio-tungstenite`
-//! crates).
+//! You can either bring your own IO (i.e. work with the `tungstenite` or `tokio-tungstenite`
+//! crates), or use one of the ready-made clients from the [`client`] module.
 //!
 //! The easiest option of getting started is looking at the [`game::Game`] trait documentation.
 //!
@@ -10,9 +10,16 @@
 //! feature is excluded from semver and is allowed to break on minor releases, because the proposed
 //! commands are not implemented on Neuro's side.
 //!
+//! The optional features `tokio` and `blocking` enable an async client based on
+//! `tokio-tungstenite` and a blocking client based on `tungstenite` respectively (see [`client`]).
+//!
+//! The optional feature `validation` allows validating action data against the registered schemas
+//! before handling it (see [`game::Session::with_validation`]).
+//!
 //! The optional feature `strip-trailing-zeroes` strips `.0` from round floating point numbers, it
 //! may be useful for slightly reducing schema/context size.
 
+pub mod client;
 pub use neuro_sama_derive as derive;
 pub mod game;
 pub mod schema;
@@ -1,10 +1,57 @@
 //! The schema as described in [the specification](https://github.com/VedalAI/neuro-game-sdk/blob/31e36c1a479faa256896a3e172c8d5a96bd462c6/API/SPECIFICATION.md).
 use std::borrow::Cow;
 
+use schemars::{
+    gen::SchemaSettings,
+    schema::{Metadata, RootSchema, SchemaObject, SubschemaValidation},
+    JsonSchema,
+};
 use serde::{Deserialize, Serialize};
 
+pub mod borrowed;
+mod lint;
+
+pub use lint::{lint, Lint, LintLevel};
+
+/// Whether the action name follows the spec: a non-empty lowercase string, with words separated
+/// by underscores or dashes (digits are allowed as well).
+pub fn is_valid_action_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+/// A JSON schema describing the wire format of the API, i.e. [`ClientCommand`] and
+/// [`ServerCommand`] (including the proposed commands if the `proposals` feature is enabled).
+///
+/// The root schema accepts any message, and the schemas for each direction are available as
+/// `#/definitions/ClientCommand` and `#/definitions/ServerCommand`. Commands this crate doesn't
+/// know about ([`ServerCommand::Unknown`]) aren't included.
+pub fn protocol_schema() -> RootSchema {
+    let mut gen = SchemaSettings::draft07().into_generator();
+    let client = gen.subschema_for::<ClientCommand>();
+    let server = gen.subschema_for::<ServerCommand>();
+    RootSchema {
+        meta_schema: gen.settings().meta_schema.clone(),
+        schema: SchemaObject {
+            metadata: Some(Box::new(Metadata {
+                title: Some("Neuro-sama game API".to_owned()),
+                description: Some("A message sent by either the game or Neuro.".to_owned()),
+                ..Default::default()
+            })),
+            subschemas: Some(Box::new(SubschemaValidation {
+                any_of: Some(vec![client, server]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        },
+        definitions: gen.take_definitions(),
+    }
+}
+
 /// A registerable command that Neuro can execute whenever she wants.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Action {
     /// The name of the action, which is its *unique identifier*. This should be a lowercase string, with words separated by underscores or dashes (e.g. `"join_friend_lobby"`, `"use_item"`).
     pub name: Cow<'static, str>,
@@ -12,12 +59,13 @@ pub struct Action {
     pub description: Cow<'static, str>,
     /// A **valid** simple JSON schema object that describes how the response data should look like. If your action does not have any parameters, you can omit this field or set it to `{}`.
     #[serde(default)]
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
     pub schema: schemars::schema::RootSchema,
 }
 
 /// Client command contents (everything except the `game` field). See `ClientCommand` docs for more
 /// info.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[non_exhaustive]
 #[serde(tag = "command", content = "data")]
 pub enum ClientCommandContents {
@@ -90,7 +138,7 @@ pub enum ClientCommandContents {
 }
 
 /// A client to server (game to Neuro) message.
-#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
 pub struct ClientCommand {
     /// The command itself.
     #[serde(flatten)]
@@ -100,7 +148,7 @@ pub struct ClientCommand {
 }
 
 /// A server to client (Neuro to game) message.
-#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
 #[serde(tag = "command", content = "data")]
 #[non_exhaustive]
 pub enum ServerCommand {
@@ -143,6 +191,64 @@ pub enum ServerCommand {
     #[cfg(feature = "proposals")]
     #[serde(rename = "shutdown/immediate")]
     ImmediateShutdown,
+    /// A command this crate doesn't know about (e.g. one added in a newer version of the API, or
+    /// a proposed command while the `proposals` feature is disabled).
+    #[serde(untagged)]
+    #[schemars(skip)]
+    Unknown {
+        /// The command's name.
+        command: String,
+        /// The command's data, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        data: Option<serde_json::Value>,
+    },
+}
+
+impl<'de> Deserialize<'de> for ServerCommand {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{DeserializeOwned, Error};
+
+        #[derive(Deserialize)]
+        struct Raw {
+            command: String,
+            #[serde(default)]
+            data: Option<serde_json::Value>,
+        }
+
+        #[derive(Deserialize)]
+        struct ActionData {
+            id: String,
+            name: String,
+            #[serde(default)]
+            data: Option<String>,
+        }
+
+        fn parse<T: DeserializeOwned, E: Error>(data: Option<serde_json::Value>) -> Result<T, E> {
+            T::deserialize(data.unwrap_or_default()).map_err(E::custom)
+        }
+
+        let Raw { command, data } = Raw::deserialize(deserializer)?;
+        Ok(match command.as_str() {
+            "action" => {
+                let ActionData { id, name, data } = parse(data)?;
+                Self::Action { id, name, data }
+            }
+            #[cfg(feature = "proposals")]
+            "actions/reregister_all" => Self::ReregisterAllActions,
+            #[cfg(feature = "proposals")]
+            "shutdown/graceful" => {
+                #[derive(Deserialize)]
+                struct Data {
+                    wants_shutdown: bool,
+                }
+                let Data { wants_shutdown } = parse(data)?;
+                Self::GracefulShutdown { wants_shutdown }
+            }
+            #[cfg(feature = "proposals")]
+            "shutdown/immediate" => Self::ImmediateShutdown,
+            _ => Self::Unknown { command, data },
+        })
+    }
 }
 
 #[cfg(test)]
@@ -190,6 +296,48 @@ mod tests {
         assert!(object_schema.required.contains("test"));
     }
 
+    #[test]
+    fn test_protocol_schema() {
+        let schema = serde_json::to_value(protocol_schema()).unwrap();
+        let commands = |name: &str| {
+            schema["definitions"][name]["oneOf"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|x| x["properties"]["command"]["enum"][0].as_str().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            commands("ClientCommand"),
+            [
+                "startup",
+                "context",
+                "actions/register",
+                "actions/unregister",
+                "actions/force",
+                "action/result",
+                #[cfg(feature = "proposals")]
+                "shutdown/ready",
+            ]
+        );
+        assert_eq!(
+            commands("ServerCommand"),
+            [
+                "action",
+                #[cfg(feature = "proposals")]
+                "actions/reregister_all",
+                #[cfg(feature = "proposals")]
+                "shutdown/graceful",
+                #[cfg(feature = "proposals")]
+                "shutdown/immediate",
+            ]
+        );
+        assert_eq!(
+            schema["definitions"]["ClientCommand"]["required"],
+            serde_json::json!(["game"])
+        );
+    }
+
     #[test]
     fn test_command_roundtrip() {
         let neuro_cmd = ServerCommand::Action {
@@ -201,6 +349,17 @@ mod tests {
         assert_eq!(parse::<ServerCommand>(SAMPLE_ACTION), neuro_cmd);
         assert_eq!(SAMPLE_ACTION, ser(&neuro_cmd));
 
+        const SAMPLE_UNKNOWN: &str = r#"{"command":"abcd","data":{"efgh":1}}"#;
+        let unknown = parse::<ServerCommand>(SAMPLE_UNKNOWN);
+        assert!(
+            matches!(unknown, ServerCommand::Unknown { ref command, data: Some(_) } if command == "abcd")
+        );
+        assert_eq!(SAMPLE_UNKNOWN, ser(&unknown));
+        assert!(
+            serde_json::from_str::<ServerCommand>(r#"{"command":"action","data":{}}"#).is_err()
+        );
+        assert!(serde_json::from_str::<ServerCommand>(r#"{"data":{}}"#).is_err());
+
         let startup = ClientCommand {
             game: "game".into(),
             command: ClientCommandContents::Startup,
@@ -0,0 +1,451 @@
+//! Borrowed versions of the schema types, which avoid allocating when parsing by borrowing
+//! strings from the input where possible. This is mostly useful for the server side.
+//!
+//! All types can be converted to their owned counterparts from the parent module via
+//! `into_owned`, and the owned types can be converted into the borrowed ones with a `'static`
+//! lifetime for free.
+//!
+//! Note that [`ServerCommand`] can only be deserialized using `serde_json` (e.g. via
+//! [`serde_json::from_str`]), since it relies on [`serde_json::value::RawValue`].
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+fn owned(s: Cow<'_, str>) -> Cow<'static, str> {
+    Cow::Owned(s.into_owned())
+}
+
+/// `serde` only borrows `Cow`s that aren't nested in other types, so this is used for nesting
+/// them instead.
+#[derive(Deserialize)]
+struct CowStr<'a>(#[serde(borrow)] Cow<'a, str>);
+
+fn borrow_opt<'de: 'a, 'a, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Cow<'a, str>>, D::Error> {
+    Ok(Option::<CowStr>::deserialize(deserializer)?.map(|x| x.0))
+}
+
+fn borrow_vec<'de: 'a, 'a, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Cow<'a, str>>, D::Error> {
+    Ok(Vec::<CowStr>::deserialize(deserializer)?
+        .into_iter()
+        .map(|x| x.0)
+        .collect())
+}
+
+/// A registerable command that Neuro can execute whenever she wants. See [`super::Action`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Action<'a> {
+    /// The name of the action, which is its *unique identifier*.
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+    /// A plaintext description of what this action does.
+    #[serde(borrow)]
+    pub description: Cow<'a, str>,
+    /// A JSON schema object that describes how the response data should look like.
+    #[serde(default)]
+    pub schema: schemars::schema::RootSchema,
+}
+
+impl Action<'_> {
+    /// Convert into the owned version.
+    pub fn into_owned(self) -> super::Action {
+        super::Action {
+            name: owned(self.name),
+            description: owned(self.description),
+            schema: self.schema,
+        }
+    }
+}
+
+impl From<super::Action> for Action<'static> {
+    fn from(action: super::Action) -> Self {
+        Self {
+            name: action.name,
+            description: action.description,
+            schema: action.schema,
+        }
+    }
+}
+
+/// Client command contents (everything except the `game` field). See
+/// [`super::ClientCommandContents`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+#[serde(tag = "command", content = "data")]
+pub enum ClientCommandContents<'a> {
+    /// See [`super::ClientCommandContents::Startup`].
+    #[serde(rename = "startup")]
+    Startup,
+    /// See [`super::ClientCommandContents::Context`].
+    #[serde(rename = "context")]
+    Context {
+        /// A plaintext message that describes what is happening in the game.
+        #[serde(borrow)]
+        message: Cow<'a, str>,
+        /// Whether Neuro shouldn't be prompted to respond to the message.
+        silent: bool,
+    },
+    /// See [`super::ClientCommandContents::RegisterActions`].
+    #[serde(rename = "actions/register")]
+    RegisterActions {
+        /// An array of actions to be registered.
+        #[serde(borrow)]
+        actions: Vec<Action<'a>>,
+    },
+    /// See [`super::ClientCommandContents::UnregisterActions`].
+    #[serde(rename = "actions/unregister")]
+    UnregisterActions {
+        /// The names of the actions to unregister.
+        #[serde(borrow, deserialize_with = "borrow_vec")]
+        action_names: Vec<Cow<'a, str>>,
+    },
+    /// See [`super::ClientCommandContents::ForceActions`].
+    #[serde(rename = "actions/force")]
+    ForceActions {
+        /// An arbitrary string that describes the current state of the game.
+        #[serde(borrow, default, deserialize_with = "borrow_opt")]
+        state: Option<Cow<'a, str>>,
+        /// A plaintext message that tells Neuro what she is currently supposed to be doing.
+        #[serde(borrow)]
+        query: Cow<'a, str>,
+        /// Whether Neuro should only remember the context for the duration of the actions force.
+        ephemeral_context: Option<bool>,
+        /// The names of the actions that Neuro should choose from.
+        #[serde(borrow, deserialize_with = "borrow_vec")]
+        action_names: Vec<Cow<'a, str>>,
+    },
+    /// See [`super::ClientCommandContents::ActionResult`].
+    #[serde(rename = "action/result")]
+    ActionResult {
+        /// The id of the action that this result is for.
+        #[serde(borrow)]
+        id: Cow<'a, str>,
+        /// Whether or not the action was successful.
+        success: bool,
+        /// A plaintext message that describes what happened when the action was executed.
+        #[serde(borrow, default, deserialize_with = "borrow_opt")]
+        message: Option<Cow<'a, str>>,
+    },
+    /// See [`super::ClientCommandContents::ShutdownReady`].
+    #[cfg(feature = "proposals")]
+    #[serde(rename = "shutdown/ready")]
+    ShutdownReady,
+}
+
+impl ClientCommandContents<'_> {
+    /// Convert into the owned version.
+    pub fn into_owned(self) -> super::ClientCommandContents {
+        match self {
+            Self::Startup => super::ClientCommandContents::Startup,
+            Self::Context { message, silent } => super::ClientCommandContents::Context {
+                message: owned(message),
+                silent,
+            },
+            Self::RegisterActions { actions } => super::ClientCommandContents::RegisterActions {
+                actions: actions.into_iter().map(Action::into_owned).collect(),
+            },
+            Self::UnregisterActions { action_names } => {
+                super::ClientCommandContents::UnregisterActions {
+                    action_names: action_names.into_iter().map(owned).collect(),
+                }
+            }
+            Self::ForceActions {
+                state,
+                query,
+                ephemeral_context,
+                action_names,
+            } => super::ClientCommandContents::ForceActions {
+                state: state.map(owned),
+                query: owned(query),
+                ephemeral_context,
+                action_names: action_names.into_iter().map(owned).collect(),
+            },
+            Self::ActionResult {
+                id,
+                success,
+                message,
+            } => super::ClientCommandContents::ActionResult {
+                id: id.into_owned(),
+                success,
+                message: message.map(owned),
+            },
+            #[cfg(feature = "proposals")]
+            Self::ShutdownReady => super::ClientCommandContents::ShutdownReady,
+        }
+    }
+}
+
+impl From<super::ClientCommandContents> for ClientCommandContents<'static> {
+    fn from(command: super::ClientCommandContents) -> Self {
+        match command {
+            super::ClientCommandContents::Startup => Self::Startup,
+            super::ClientCommandContents::Context { message, silent } => {
+                Self::Context { message, silent }
+            }
+            super::ClientCommandContents::RegisterActions { actions } => Self::RegisterActions {
+                actions: actions.into_iter().map(Into::into).collect(),
+            },
+            super::ClientCommandContents::UnregisterActions { action_names } => {
+                Self::UnregisterActions { action_names }
+            }
+            super::ClientCommandContents::ForceActions {
+                state,
+                query,
+                ephemeral_context,
+                action_names,
+            } => Self::ForceActions {
+                state,
+                query,
+                ephemeral_context,
+                action_names,
+            },
+            super::ClientCommandContents::ActionResult {
+                id,
+                success,
+                message,
+            } => Self::ActionResult {
+                id: id.into(),
+                success,
+                message,
+            },
+            #[cfg(feature = "proposals")]
+            super::ClientCommandContents::ShutdownReady => Self::ShutdownReady,
+        }
+    }
+}
+
+/// A client to server (game to Neuro) message. See [`super::ClientCommand`].
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ClientCommand<'a> {
+    /// The command itself.
+    #[serde(flatten, borrow)]
+    pub command: ClientCommandContents<'a>,
+    /// The game name.
+    #[serde(borrow)]
+    pub game: Cow<'a, str>,
+}
+
+impl ClientCommand<'_> {
+    /// Convert into the owned version.
+    pub fn into_owned(self) -> super::ClientCommand {
+        super::ClientCommand {
+            command: self.command.into_owned(),
+            game: owned(self.game),
+        }
+    }
+}
+
+impl From<super::ClientCommand> for ClientCommand<'static> {
+    fn from(command: super::ClientCommand) -> Self {
+        Self {
+            command: command.command.into(),
+            game: command.game,
+        }
+    }
+}
+
+/// A server to client (Neuro to game) message. See [`super::ServerCommand`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "command", content = "data")]
+#[non_exhaustive]
+pub enum ServerCommand<'a> {
+    /// See [`super::ServerCommand::Action`].
+    #[serde(rename = "action")]
+    Action {
+        /// A unique id for the action.
+        id: Cow<'a, str>,
+        /// The name of the action that Neuro is trying to execute.
+        name: Cow<'a, str>,
+        /// The JSON-stringified data for the action, as sent by Neuro.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        data: Option<Cow<'a, str>>,
+    },
+    /// See [`super::ServerCommand::ReregisterAllActions`].
+    #[cfg(feature = "proposals")]
+    #[serde(rename = "actions/reregister_all")]
+    ReregisterAllActions,
+    /// See [`super::ServerCommand::GracefulShutdown`].
+    #[cfg(feature = "proposals")]
+    #[serde(rename = "shutdown/graceful")]
+    GracefulShutdown {
+        /// Whether the game should shutdown at the next graceful shutdown point.
+        wants_shutdown: bool,
+    },
+    /// See [`super::ServerCommand::ImmediateShutdown`].
+    #[cfg(feature = "proposals")]
+    #[serde(rename = "shutdown/immediate")]
+    ImmediateShutdown,
+    /// See [`super::ServerCommand::Unknown`].
+    #[serde(untagged)]
+    Unknown {
+        /// The command's name.
+        command: Cow<'a, str>,
+        /// The command's data, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        data: Option<serde_json::Value>,
+    },
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for ServerCommand<'a> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(Deserialize)]
+        struct Raw<'a> {
+            #[serde(borrow)]
+            command: Cow<'a, str>,
+            #[serde(borrow, default)]
+            data: Option<&'a RawValue>,
+        }
+
+        #[derive(Deserialize)]
+        struct ActionData<'a> {
+            #[serde(borrow)]
+            id: Cow<'a, str>,
+            #[serde(borrow)]
+            name: Cow<'a, str>,
+            #[serde(borrow, default, deserialize_with = "borrow_opt")]
+            data: Option<Cow<'a, str>>,
+        }
+
+        fn parse<'a, T: Deserialize<'a>, E: Error>(data: Option<&'a RawValue>) -> Result<T, E> {
+            let data = data.map_or("null", RawValue::get);
+            serde_json::from_str(data).map_err(E::custom)
+        }
+
+        let Raw { command, data } = Raw::deserialize(deserializer)?;
+        Ok(match &*command {
+            "action" => {
+                let ActionData { id, name, data } = parse(data)?;
+                Self::Action { id, name, data }
+            }
+            #[cfg(feature = "proposals")]
+            "actions/reregister_all" => Self::ReregisterAllActions,
+            #[cfg(feature = "proposals")]
+            "shutdown/graceful" => {
+                #[derive(Deserialize)]
+                struct Data {
+                    wants_shutdown: bool,
+                }
+                let Data { wants_shutdown } = parse(data)?;
+                Self::GracefulShutdown { wants_shutdown }
+            }
+            #[cfg(feature = "proposals")]
+            "shutdown/immediate" => Self::ImmediateShutdown,
+            _ => Self::Unknown {
+                command,
+                data: data.map(|x| parse(Some(x))).transpose()?,
+            },
+        })
+    }
+}
+
+impl ServerCommand<'_> {
+    /// Convert into the owned version.
+    pub fn into_owned(self) -> super::ServerCommand {
+        match self {
+            Self::Action { id, name, data } => super::ServerCommand::Action {
+                id: id.into_owned(),
+                name: name.into_owned(),
+                data: data.map(Cow::into_owned),
+            },
+            #[cfg(feature = "proposals")]
+            Self::ReregisterAllActions => super::ServerCommand::ReregisterAllActions,
+            #[cfg(feature = "proposals")]
+            Self::GracefulShutdown { wants_shutdown } => {
+                super::ServerCommand::GracefulShutdown { wants_shutdown }
+            }
+            #[cfg(feature = "proposals")]
+            Self::ImmediateShutdown => super::ServerCommand::ImmediateShutdown,
+            Self::Unknown { command, data } => super::ServerCommand::Unknown {
+                command: command.into_owned(),
+                data,
+            },
+        }
+    }
+}
+
+impl From<super::ServerCommand> for ServerCommand<'static> {
+    fn from(command: super::ServerCommand) -> Self {
+        match command {
+            super::ServerCommand::Action { id, name, data } => Self::Action {
+                id: id.into(),
+                name: name.into(),
+                data: data.map(Into::into),
+            },
+            #[cfg(feature = "proposals")]
+            super::ServerCommand::ReregisterAllActions => Self::ReregisterAllActions,
+            #[cfg(feature = "proposals")]
+            super::ServerCommand::GracefulShutdown { wants_shutdown } => {
+                Self::GracefulShutdown { wants_shutdown }
+            }
+            #[cfg(feature = "proposals")]
+            super::ServerCommand::ImmediateShutdown => Self::ImmediateShutdown,
+            super::ServerCommand::Unknown { command, data } => Self::Unknown {
+                command: command.into(),
+                data,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use super::{ClientCommand, ClientCommandContents, ServerCommand};
+
+    #[test]
+    fn test_borrowed() {
+        let text = r#"{"command":"action","data":{"id":"abcd","name":"efgh","data":"{\"x\":1}"}}"#;
+        let cmd: ServerCommand = serde_json::from_str(text).unwrap();
+        let ServerCommand::Action { id, name, data } = &cmd else {
+            panic!()
+        };
+        assert!(matches!(id, Cow::Borrowed("abcd")));
+        assert!(matches!(name, Cow::Borrowed("efgh")));
+        // escaped strings can't be borrowed
+        assert!(matches!(data.as_deref(), Some(r#"{"x":1}"#)));
+        assert_eq!(crate::to_string(&cmd).unwrap(), text);
+        assert_eq!(
+            cmd.into_owned(),
+            serde_json::from_str::<crate::schema::ServerCommand>(text).unwrap()
+        );
+        let cmd: ServerCommand = serde_json::from_str(r#"{"command":"test"}"#).unwrap();
+        assert!(matches!(
+            cmd,
+            ServerCommand::Unknown {
+                command: Cow::Borrowed("test"),
+                data: None
+            }
+        ));
+        assert!(serde_json::from_str::<ServerCommand>(r#"{"command":"action"}"#).is_err());
+
+        let text =
+            r#"{"command":"actions/unregister","data":{"action_names":["a","b"]},"game":"game"}"#;
+        let cmd: ClientCommand = serde_json::from_str(text).unwrap();
+        let ClientCommandContents::UnregisterActions { action_names } = &cmd.command else {
+            panic!()
+        };
+        assert!(matches!(action_names[0], Cow::Borrowed("a")));
+        let force: ClientCommand = serde_json::from_str(
+            r#"{"command":"actions/force","data":{"query":"q","action_names":[]},"game":"game"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            force.command,
+            ClientCommandContents::ForceActions { state: None, .. }
+        ));
+        assert!(matches!(cmd.game, Cow::Borrowed("game")));
+        let owned = cmd.into_owned();
+        assert_eq!(
+            owned,
+            serde_json::from_str::<crate::schema::ClientCommand>(text).unwrap()
+        );
+        assert_eq!(crate::to_string(&ClientCommand::from(owned)).unwrap(), text);
+    }
+}
@@ -0,0 +1,325 @@
+//! Checking schemas for constructs Neuro might not handle well
+use std::{borrow::Cow, fmt};
+
+use schemars::schema::{RootSchema, Schema, SchemaObject, SingleOrVec};
+
+/// How bad a [`Lint`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintLevel {
+    /// The construct is likely to work, but might confuse Neuro.
+    Risky,
+    /// The construct isn't supported by Neuro.
+    Unsupported,
+}
+
+/// A problem found by [`lint`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lint {
+    /// A JSON pointer to the schema object the keyword is in (the root schema is `""`).
+    pub path: String,
+    /// The offending keyword.
+    pub keyword: &'static str,
+    /// How bad the problem is.
+    pub level: LintLevel,
+    /// A human-readable explanation.
+    pub message: Cow<'static, str>,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            LintLevel::Risky => "risky",
+            LintLevel::Unsupported => "unsupported",
+        };
+        let path = if self.path.is_empty() {
+            "(root)"
+        } else {
+            &self.path
+        };
+        write!(f, "{path}: {level} `{}`: {}", self.keyword, self.message)
+    }
+}
+
+/// Formats defined by the JSON schema spec, anything else (e.g. `uint32`, which `schemars`
+/// generates for Rust integers) is non-standard.
+const STANDARD_FORMATS: &[&str] = &[
+    "date-time",
+    "date",
+    "time",
+    "duration",
+    "email",
+    "idn-email",
+    "hostname",
+    "idn-hostname",
+    "ipv4",
+    "ipv6",
+    "uri",
+    "uri-reference",
+    "iri",
+    "iri-reference",
+    "uuid",
+    "uri-template",
+    "json-pointer",
+    "relative-json-pointer",
+    "regex",
+];
+
+/// Walk the schema and report keywords that Neuro doesn't support or might handle badly.
+///
+/// Neuro only supports a simple subset of JSON schema. In particular, references (which
+/// `schemars` generates for nested structs), conditionals and pattern-based properties are
+/// unsupported, while composition keywords and non-standard formats are risky.
+pub fn lint(schema: &RootSchema) -> Vec<Lint> {
+    let mut linter = Linter::default();
+    linter.visit_obj(&schema.schema, &mut String::new());
+    for (name, schema) in &schema.definitions {
+        let mut path = format!("/definitions/{}", escape(name));
+        linter.push(
+            &path,
+            "definitions",
+            LintLevel::Unsupported,
+            "definitions are only useful with `$ref`, which isn't supported; inline the schema \
+             instead",
+        );
+        linter.visit(schema, &mut path);
+    }
+    linter.lints
+}
+
+/// Escape a JSON pointer segment.
+fn escape(segment: &str) -> Cow<'_, str> {
+    if segment.contains(['~', '/']) {
+        Cow::Owned(segment.replace('~', "~0").replace('/', "~1"))
+    } else {
+        Cow::Borrowed(segment)
+    }
+}
+
+#[derive(Default)]
+struct Linter {
+    lints: Vec<Lint>,
+}
+
+impl Linter {
+    fn push(
+        &mut self,
+        path: &str,
+        keyword: &'static str,
+        level: LintLevel,
+        message: impl Into<Cow<'static, str>>,
+    ) {
+        self.lints.push(Lint {
+            path: path.to_owned(),
+            keyword,
+            level,
+            message: message.into(),
+        });
+    }
+
+    /// Visit a subschema, appending `segment` to the path for the duration of the call.
+    fn visit_at(&mut self, schema: &Schema, path: &mut String, segment: &str) {
+        let len = path.len();
+        path.push('/');
+        path.push_str(segment);
+        self.visit(schema, path);
+        path.truncate(len);
+    }
+
+    fn visit_all(&mut self, schemas: &SingleOrVec<Schema>, path: &mut String, keyword: &str) {
+        match schemas {
+            SingleOrVec::Single(schema) => self.visit_at(schema, path, keyword),
+            SingleOrVec::Vec(schemas) => {
+                for (i, schema) in schemas.iter().enumerate() {
+                    self.visit_at(schema, path, &format!("{keyword}/{i}"));
+                }
+            }
+        }
+    }
+
+    fn visit(&mut self, schema: &Schema, path: &mut String) {
+        match schema {
+            Schema::Object(obj) => self.visit_obj(obj, path),
+            Schema::Bool(_) => {}
+        }
+    }
+
+    fn visit_obj(&mut self, schema: &SchemaObject, path: &mut String) {
+        if let Some(reference) = &schema.reference {
+            self.push(
+                path,
+                "$ref",
+                LintLevel::Unsupported,
+                format!(
+                    "references (to `{reference}`) aren't supported, inline the schema instead"
+                ),
+            );
+        }
+        if let Some(format) = &schema.format {
+            if !STANDARD_FORMATS.contains(&format.as_str()) {
+                self.push(
+                    path,
+                    "format",
+                    LintLevel::Risky,
+                    format!("`{format}` is not a standard format"),
+                );
+            }
+        }
+        if let Some(sub) = &schema.subschemas {
+            for (keyword, schemas) in [
+                ("allOf", &sub.all_of),
+                ("anyOf", &sub.any_of),
+                ("oneOf", &sub.one_of),
+            ] {
+                let Some(schemas) = schemas else {
+                    continue;
+                };
+                if keyword != "anyOf" {
+                    self.push(
+                        path,
+                        keyword,
+                        LintLevel::Risky,
+                        "schema composition might confuse Neuro, prefer `anyOf` or `enum`",
+                    );
+                }
+                for (i, schema) in schemas.iter().enumerate() {
+                    self.visit_at(schema, path, &format!("{keyword}/{i}"));
+                }
+            }
+            for (keyword, schema) in [
+                ("not", &sub.not),
+                ("if", &sub.if_schema),
+                ("then", &sub.then_schema),
+                ("else", &sub.else_schema),
+            ] {
+                let Some(schema) = schema else {
+                    continue;
+                };
+                self.push(
+                    path,
+                    keyword,
+                    LintLevel::Unsupported,
+                    if keyword == "not" {
+                        "negated schemas aren't supported"
+                    } else {
+                        "conditional schemas aren't supported"
+                    },
+                );
+                self.visit_at(schema, path, keyword);
+            }
+        }
+        if let Some(arr) = &schema.array {
+            if let Some(items) = &arr.items {
+                if matches!(items, SingleOrVec::Vec(_)) {
+                    self.push(
+                        path,
+                        "items",
+                        LintLevel::Risky,
+                        "tuple schemas might confuse Neuro, prefer an object with named fields",
+                    );
+                }
+                self.visit_all(items, path, "items");
+            }
+            if let Some(schema) = &arr.additional_items {
+                self.push(
+                    path,
+                    "additionalItems",
+                    LintLevel::Unsupported,
+                    "`additionalItems` isn't supported",
+                );
+                self.visit_at(schema, path, "additionalItems");
+            }
+            if let Some(schema) = &arr.contains {
+                self.push(
+                    path,
+                    "contains",
+                    LintLevel::Unsupported,
+                    "`contains` isn't supported",
+                );
+                self.visit_at(schema, path, "contains");
+            }
+        }
+        if let Some(obj) = &schema.object {
+            for (name, schema) in &obj.properties {
+                self.visit_at(schema, path, &format!("properties/{}", escape(name)));
+            }
+            for (pattern, schema) in &obj.pattern_properties {
+                self.push(
+                    path,
+                    "patternProperties",
+                    LintLevel::Unsupported,
+                    format!("pattern properties (`{pattern}`) aren't supported"),
+                );
+                self.visit_at(
+                    schema,
+                    path,
+                    &format!("patternProperties/{}", escape(pattern)),
+                );
+            }
+            if let Some(schema) = &obj.additional_properties {
+                if matches!(**schema, Schema::Object(_)) {
+                    self.push(
+                        path,
+                        "additionalProperties",
+                        LintLevel::Risky,
+                        "additional properties with a schema might confuse Neuro, prefer an \
+                         array of objects",
+                    );
+                }
+                self.visit_at(schema, path, "additionalProperties");
+            }
+            if let Some(schema) = &obj.property_names {
+                self.push(
+                    path,
+                    "propertyNames",
+                    LintLevel::Unsupported,
+                    "`propertyNames` isn't supported",
+                );
+                self.visit_at(schema, path, "propertyNames");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use schemars::JsonSchema;
+
+    use super::{lint, LintLevel};
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    struct Inner {
+        x: u32,
+    }
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    struct Outer {
+        inner: Inner,
+        #[serde(rename = "a/b")]
+        name: u8,
+    }
+
+    #[test]
+    fn test_lint() {
+        let lints = lint(&schemars::schema_for!(Outer));
+        let lints = lints
+            .iter()
+            .map(|x| (x.path.as_str(), x.keyword, x.level))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lints,
+            [
+                ("/properties/a~1b", "format", LintLevel::Risky),
+                ("/properties/inner", "$ref", LintLevel::Unsupported),
+                ("/definitions/Inner", "definitions", LintLevel::Unsupported),
+                (
+                    "/definitions/Inner/properties/x",
+                    "format",
+                    LintLevel::Risky
+                ),
+            ]
+        );
+        assert!(lint(&schemars::schema_for!(String)).is_empty());
+    }
+}
@@ -1,12 +1,10 @@
 use std::{sync::Arc, time::Duration};
 
-use futures_util::{SinkExt, StreamExt};
-use neuro_sama::game::Api;
+use neuro_sama::{client, game::Api};
 use schemars::JsonSchema;
 use serde::Deserialize;
-use tokio::sync::mpsc;
 
-struct TestGame(mpsc::UnboundedSender<tungstenite::Message>);
+struct TestGame(client::tokio::Sender);
 
 #[allow(unused)]
 #[derive(Debug, Deserialize, JsonSchema)]
@@ -45,7 +43,13 @@ impl neuro_sama::game::Game for TestGame {
     const NAME: &'static str = "Test Game";
     type Actions<'a> = Action;
     fn send_command(&self, message: tungstenite::Message) {
-        let _ = self.0.send(message);
+        let _ = self.try_send_command(message);
+    }
+    fn try_send_command(
+        &self,
+        message: tungstenite::Message,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.0.send(message)?)
     }
     fn reregister_actions(&self) {
         // your game could have some complicated logic here i guess
@@ -74,9 +78,8 @@ impl neuro_sama::game::Game for TestGame {
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    let (game2ws_tx, mut game2ws_rx) = mpsc::unbounded_channel();
+    let (game2ws_tx, game2ws_rx) = client::tokio::channel();
     let game = Arc::new(TestGame(game2ws_tx));
-    game.initialize().unwrap();
     let game1 = game.clone();
     tokio::spawn(async move {
         loop {
@@ -88,43 +91,17 @@ async fn main() {
                 .unwrap();
         }
     });
-    let mut ws =
-        tokio_tungstenite::connect_async(if let Ok(url) = std::env::var("NEURO_SDK_WS_URL") {
+    let mut client = client::tokio::run(
+        game,
+        game2ws_rx,
+        if let Ok(url) = std::env::var("NEURO_SDK_WS_URL") {
             url
         } else {
             "ws://127.0.0.1:8000".to_owned()
-        })
-        .await
-        .unwrap()
-        .0;
-    loop {
-        tokio::select! {
-            msg = game2ws_rx.recv() => {
-                println!("game2ws {msg:?}");
-                let Some(msg) = msg else {
-                    break;
-                };
-                if ws.send(msg).await.is_err() {
-                    println!("websocket send failed");
-                    break;
-                }
-            }
-            msg = ws.next() => {
-                println!("ws2game {msg:?}");
-                let Some(msg) = msg else {
-                    break;
-                };
-                let Ok(msg) = msg else {
-                    continue;
-                };
-                if let Err(err) = game.handle_message(msg) {
-                    // this could happen because we don't know what this message means (e.g. added
-                    // in a new version of the API)
-                    println!("notify_message failed: {err}");
-                    continue;
-                }
-            }
-
-        }
+        },
+    )
+    .with_reconnect(client::Backoff::new());
+    while let Some(event) = client.next_event().await {
+        println!("{event:?}");
     }
 }